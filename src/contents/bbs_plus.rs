@@ -0,0 +1,109 @@
+use super::bls;
+use crate::Error;
+use bbs::prelude::{
+    DeterministicPublicKey, HashElem, HiddenMessage, ProofChallenge, ProofMessage, ProofNonce,
+    Prover, PublicKey, SecretKey, Signature, SignatureMessage, SignatureProof, Verifier,
+};
+use std::convert::TryFrom;
+
+/// Signs `messages` with `Bls12381G2Key2020` key as one BBS+ signature
+pub(crate) fn sign<M: AsRef<[u8]>>(
+    private_key: &[u8],
+    public_key: &[u8],
+    messages: &[M],
+) -> Result<Vec<u8>, Error> {
+    let sk = SecretKey::from(bls::secret_key(private_key)?.to_bytes());
+    let pk = bbs_public_key(public_key, messages.len())?;
+    let signature = Signature::new(&hash_messages(messages), &sk, &pk).map_err(bbs_error)?;
+    Ok(signature.to_bytes_compressed_form().to_vec())
+}
+
+/// Verifies BBS+ signature of all the `messages`
+pub(crate) fn verify<M: AsRef<[u8]>>(
+    public_key: &[u8],
+    messages: &[M],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    let pk = bbs_public_key(public_key, messages.len())?;
+    Signature::try_from(signature)
+        .map_err(bbs_error)?
+        .verify(&hash_messages(messages), &pk)
+        .map_err(bbs_error)
+}
+
+/// Derives zero-knowledge proof of the signature, which discloses only
+/// the messages at `revealed` indexes and is bound to the verifier's `nonce`
+pub(crate) fn derive_proof<M: AsRef<[u8]>>(
+    public_key: &[u8],
+    messages: &[M],
+    signature: &[u8],
+    revealed: &[usize],
+    nonce: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let pk = bbs_public_key(public_key, messages.len())?;
+    let request = Verifier::new_proof_request(revealed, &pk).map_err(bbs_error)?;
+    let proof_messages = hash_messages(messages)
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            if revealed.contains(&i) {
+                ProofMessage::Revealed(m)
+            } else {
+                ProofMessage::Hidden(HiddenMessage::ProofSpecificBlinding(m))
+            }
+        })
+        .collect::<Vec<_>>();
+    let signature = Signature::try_from(signature).map_err(bbs_error)?;
+    let pok =
+        Prover::commit_signature_pok(&request, &proof_messages, &signature).map_err(bbs_error)?;
+    let mut challenge = pok.to_bytes();
+    challenge.extend_from_slice(&ProofNonce::hash(nonce).to_bytes_uncompressed_form()[..]);
+    let proof = Prover::generate_signature_pok(pok, &ProofChallenge::hash(&challenge))
+        .map_err(bbs_error)?;
+    Ok(proof.to_bytes_compressed_form())
+}
+
+/// Verifies proof made by `derive_proof` against the disclosed messages
+pub(crate) fn verify_proof<M: AsRef<[u8]>>(
+    public_key: &[u8],
+    message_count: usize,
+    revealed: &[(usize, M)],
+    proof: &[u8],
+    nonce: &[u8],
+) -> Result<bool, Error> {
+    let pk = bbs_public_key(public_key, message_count)?;
+    let mut revealed = revealed
+        .iter()
+        .map(|(i, m)| (*i, SignatureMessage::hash(m.as_ref())))
+        .collect::<Vec<_>>();
+    revealed.sort_by_key(|(i, _)| *i);
+    let indexes = revealed.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+    let request = Verifier::new_proof_request(&indexes, &pk).map_err(bbs_error)?;
+    let proof = SignatureProof::try_from(proof).map_err(bbs_error)?;
+    match Verifier::verify_signature_pok(&request, &proof, &ProofNonce::hash(nonce)) {
+        Ok(disclosed) => Ok(disclosed
+            .into_iter()
+            .eq(revealed.into_iter().map(|(_, m)| m))),
+        Err(_) => Ok(false),
+    }
+}
+
+// BBS+ key for `message_count` messages of `Bls12381G2Key2020` public key
+fn bbs_public_key(public_key: &[u8], message_count: usize) -> Result<PublicKey, Error> {
+    DeterministicPublicKey::try_from(public_key)
+        .and_then(|dpk| dpk.to_public_key(message_count))
+        .map_err(bbs_error)
+}
+
+// messages are signed as their hashes
+fn hash_messages<M: AsRef<[u8]>>(messages: &[M]) -> Vec<SignatureMessage> {
+    messages
+        .iter()
+        .map(|m| SignatureMessage::hash(m.as_ref()))
+        .collect()
+}
+
+// BBS+ failures are reported as BLS ones
+fn bbs_error(e: bbs::prelude::BBSError) -> Error {
+    Error::BlsCryptoError(e.to_string())
+}
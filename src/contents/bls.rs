@@ -0,0 +1,260 @@
+use super::public_key_info::{KeyType, PublicKeyInfo};
+use crate::Error;
+use signature_bls::{
+    AggregateSignature, AggregateSignatureVt, MultiPublicKey, MultiPublicKeyVt, ProofOfPossession,
+    ProofOfPossessionVt, PublicKey, PublicKeyVt, SecretKey, Signature, SignatureVt,
+};
+use std::convert::TryFrom;
+
+/// Aggregates signatures made by keys of `key_type` into one signature
+///
+/// # Parameters
+///
+/// * key_type - `Bls12381G1Key2020` or `Bls12381G2Key2020`
+/// * signatures - signatures to aggregate, either over the same or distinct messages
+///
+pub fn aggregate_signatures<S: AsRef<[u8]>>(
+    key_type: KeyType,
+    signatures: &[S],
+) -> Result<Vec<u8>, Error> {
+    if signatures.is_empty() {
+        return Err(Error::BlsCryptoError("no signatures to aggregate".into()));
+    }
+    match key_type {
+        KeyType::Bls12381G1Key2020 => {
+            let signatures = signatures
+                .iter()
+                .map(|s| g1_key_signature(s.as_ref()))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(AggregateSignatureVt::from(&signatures[..])
+                .to_bytes()
+                .to_vec())
+        }
+        KeyType::Bls12381G2Key2020 => {
+            let signatures = signatures
+                .iter()
+                .map(|s| g2_key_signature(s.as_ref()))
+                .collect::<Result<Vec<_>, Error>>()?;
+            Ok(AggregateSignature::from(&signatures[..])
+                .to_bytes()
+                .to_vec())
+        }
+        _ => Err(Error::WrongKeyType),
+    }
+}
+
+/// Aggregates public keys of the same BLS type into one key,
+/// which verifies aggregated signatures over the same message.
+/// Proofs of possession of all the keys must be checked beforehand.
+///
+/// # Parameters
+///
+/// * public_keys - `Bls12381G1Key2020` or `Bls12381G2Key2020` keys
+///
+pub fn aggregate_public_keys(public_keys: &[PublicKeyInfo]) -> Result<PublicKeyInfo, Error> {
+    let key_type = common_key_type(public_keys)?;
+    let public_key = match key_type {
+        KeyType::Bls12381G1Key2020 => g1_multi_public_key(public_keys)?.to_bytes().to_vec(),
+        _ => g2_multi_public_key(public_keys)?.to_bytes().to_vec(),
+    };
+    Ok(PublicKeyInfo::new(key_type, &public_key))
+}
+
+/// Verifies signature aggregated from signatures of the same message.
+/// Proofs of possession of all the keys must be checked beforehand.
+///
+/// # Parameters
+///
+/// * public_keys - keys of all the signers
+/// * data - message signed by every key
+/// * signature - aggregated signature
+///
+pub fn verify_aggregate(
+    public_keys: &[PublicKeyInfo],
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    let valid = match common_key_type(public_keys)? {
+        KeyType::Bls12381G1Key2020 => {
+            g1_key_aggregate(signature)?.verify(g1_multi_public_key(public_keys)?, data)
+        }
+        _ => g2_key_aggregate(signature)?.verify(g2_multi_public_key(public_keys)?, data),
+    };
+    Ok(valid.unwrap_u8() == 1u8)
+}
+
+/// Verifies signature aggregated from signatures of distinct messages.
+/// Fails verification if any message repeats.
+///
+/// # Parameters
+///
+/// * public_keys - keys of all the signers
+/// * messages - messages in the order of `public_keys`
+/// * signature - aggregated signature
+///
+pub fn verify_aggregate_distinct<M: AsRef<[u8]>>(
+    public_keys: &[PublicKeyInfo],
+    messages: &[M],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    let key_type = common_key_type(public_keys)?;
+    if messages.len() != public_keys.len() {
+        return Err(Error::BlsCryptoError(format!(
+            "{} messages for {} public keys",
+            messages.len(),
+            public_keys.len()
+        )));
+    }
+    let valid = match key_type {
+        KeyType::Bls12381G1Key2020 => {
+            let signed = public_keys
+                .iter()
+                .zip(messages)
+                .map(|(pk, m)| Ok((g1_public_key(&pk.resolve_multikey()?.public_key)?, m)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            g1_key_aggregate(signature)?.verify_msgs(&signed)
+        }
+        _ => {
+            let signed = public_keys
+                .iter()
+                .zip(messages)
+                .map(|(pk, m)| Ok((g2_public_key(&pk.resolve_multikey()?.public_key)?, m)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            g2_key_aggregate(signature)?.verify_msgs(&signed)
+        }
+    };
+    Ok(valid.unwrap_u8() == 1u8)
+}
+
+/// Parses BLS12-381 secret key, shared by G1 and G2 keys
+pub(crate) fn secret_key(bytes: &[u8]) -> Result<SecretKey, Error> {
+    parse(bytes, "secret key", |b| SecretKey::from_bytes(b).into())
+}
+
+/// Parses public key of `Bls12381G1Key2020`
+pub(crate) fn g1_public_key(bytes: &[u8]) -> Result<PublicKeyVt, Error> {
+    parse(bytes, "G1 public key", |b| {
+        PublicKeyVt::from_bytes(b).into()
+    })
+}
+
+/// Parses public key of `Bls12381G2Key2020`
+pub(crate) fn g2_public_key(bytes: &[u8]) -> Result<PublicKey, Error> {
+    parse(bytes, "G2 public key", |b| PublicKey::from_bytes(b).into())
+}
+
+/// Parses signature of `Bls12381G1Key2020`, which is a G2 point
+pub(crate) fn g1_key_signature(bytes: &[u8]) -> Result<SignatureVt, Error> {
+    parse(bytes, "G1 key signature", |b| {
+        SignatureVt::from_bytes(b).into()
+    })
+}
+
+/// Parses signature of `Bls12381G2Key2020`, which is a G1 point
+pub(crate) fn g2_key_signature(bytes: &[u8]) -> Result<Signature, Error> {
+    parse(bytes, "G2 key signature", |b| {
+        Signature::from_bytes(b).into()
+    })
+}
+
+/// Parses proof of possession of `Bls12381G1Key2020`
+pub(crate) fn g1_key_possession_proof(bytes: &[u8]) -> Result<ProofOfPossessionVt, Error> {
+    parse(bytes, "proof of possession", |b| {
+        ProofOfPossessionVt::from_bytes(b).into()
+    })
+}
+
+/// Parses proof of possession of `Bls12381G2Key2020`
+pub(crate) fn g2_key_possession_proof(bytes: &[u8]) -> Result<ProofOfPossession, Error> {
+    parse(bytes, "proof of possession", |b| {
+        ProofOfPossession::from_bytes(b).into()
+    })
+}
+
+// BLS type shared by all `public_keys`, `Multikey`s resolved
+fn common_key_type(public_keys: &[PublicKeyInfo]) -> Result<KeyType, Error> {
+    let mut key_types = public_keys
+        .iter()
+        .map(|pk| Ok(pk.resolve_multikey()?.key_type))
+        .collect::<Result<Vec<_>, Error>>()?;
+    key_types.dedup();
+    match key_types[..] {
+        [] => Err(Error::BlsCryptoError("no public keys".into())),
+        [key_type @ KeyType::Bls12381G1Key2020] | [key_type @ KeyType::Bls12381G2Key2020] => {
+            Ok(key_type)
+        }
+        _ => Err(Error::WrongKeyType),
+    }
+}
+
+// sum of `Bls12381G1Key2020` public keys
+fn g1_multi_public_key(public_keys: &[PublicKeyInfo]) -> Result<MultiPublicKeyVt, Error> {
+    let keys = public_keys
+        .iter()
+        .map(|pk| g1_public_key(&pk.resolve_multikey()?.public_key))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(MultiPublicKeyVt::from(&keys[..]))
+}
+
+// sum of `Bls12381G2Key2020` public keys
+fn g2_multi_public_key(public_keys: &[PublicKeyInfo]) -> Result<MultiPublicKey, Error> {
+    let keys = public_keys
+        .iter()
+        .map(|pk| g2_public_key(&pk.resolve_multikey()?.public_key))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(MultiPublicKey::from(&keys[..]))
+}
+
+// aggregated signature of `Bls12381G1Key2020` keys
+fn g1_key_aggregate(bytes: &[u8]) -> Result<AggregateSignatureVt, Error> {
+    parse(bytes, "aggregated signature", |b| {
+        AggregateSignatureVt::from_bytes(b).into()
+    })
+}
+
+// aggregated signature of `Bls12381G2Key2020` keys
+fn g2_key_aggregate(bytes: &[u8]) -> Result<AggregateSignature, Error> {
+    parse(bytes, "aggregated signature", |b| {
+        AggregateSignature::from_bytes(b).into()
+    })
+}
+
+// checks length of `bytes` before parsing them, as `signature_bls` takes fixed size arrays
+fn parse<T, const N: usize>(
+    bytes: &[u8],
+    name: &str,
+    from_bytes: impl Fn(&[u8; N]) -> Option<T>,
+) -> Result<T, Error> {
+    let array = <&[u8; N]>::try_from(bytes).map_err(|_| {
+        Error::BlsCryptoError(format!("{} must be {} bytes, got {}", name, N, bytes.len()))
+    })?;
+    from_bytes(array).ok_or_else(|| Error::BlsCryptoError(format!("invalid {}", name)))
+}
+
+#[test]
+fn malformed_bytes_are_rejected() {
+    assert!(matches!(
+        secret_key(&[1u8; 31]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        secret_key(&[0xffu8; 32]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        g1_public_key(&[0u8; 96]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        g2_public_key(&[0xffu8; 96]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        g1_key_signature(&[]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        g2_key_signature(&[0u8; 96]),
+        Err(Error::BlsCryptoError(_))
+    ));
+}
@@ -0,0 +1,268 @@
+use super::public_key_info::KeyType;
+use crate::Error;
+use hmac::{Hmac, Mac};
+use k256::{ecdsa::SigningKey, elliptic_curve::PrimeField, FieldBytes, Scalar};
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+/// Offset of hardened child indexes
+pub const HARDENED: u32 = 0x8000_0000;
+
+// private key and chain code of a node in the derivation tree
+struct ExtendedKey {
+    key: Zeroizing<Vec<u8>>,
+    chain_code: Zeroizing<Vec<u8>>,
+}
+
+/// Derives private key of `key_type` from the seed along the derivation path.
+/// secp256k1 keys are derived with [BIP32](https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki),
+/// Ed25519 and X25519 ones with [SLIP-0010](https://github.com/satoshilabs/slips/blob/master/slip-0010.md),
+/// which supports hardened indexes only.
+///
+/// # Parameters
+///
+/// * key_type - `KeyType` of the derived key
+/// * seed - master seed, e.g. `Entropy` stored in the wallet
+/// * path - derivation path like `m/44'/60'/0'/0/0`, `h` also marks hardened indexes
+///
+pub fn derive_private_key(
+    key_type: KeyType,
+    seed: &[u8],
+    path: &str,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let indexes = parse_path(path)?;
+    let curve: &[u8] = match key_type {
+        KeyType::EcdsaSecp256k1VerificationKey2019
+        | KeyType::EcdsaSecp256k1RecoveryMethod2020
+        | KeyType::SchnorrSecp256k1VerificationKey2019 => {
+            let mut node = bip32_master(seed)?;
+            for index in indexes {
+                node = bip32_child(&node, index)?;
+            }
+            return Ok(node.key);
+        }
+        KeyType::Ed25519VerificationKey2018 | KeyType::Ed25519VerificationKey2020 => {
+            b"ed25519 seed"
+        }
+        KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+            b"curve25519 seed"
+        }
+        _ => return Err(Error::UnsupportedKeyType),
+    };
+    if indexes.iter().any(|index| *index < HARDENED) {
+        return Err(Error::InvalidDerivationPath(path.to_string()));
+    }
+    let mut node = hmac_sha512(curve, &[seed])?;
+    for index in indexes {
+        node = slip10_hardened_child(&node, index)?;
+    }
+    Ok(node.key)
+}
+
+/// [BIP44](https://github.com/bitcoin/bips/blob/master/bip-0044.mediawiki) derivation path
+/// `m/44'/coin_type'/account'/change/index`
+///
+/// # Parameters
+///
+/// * coin_type - [SLIP44](https://github.com/satoshilabs/slips/blob/master/slip-0044.md) coin type, e.g. 60 for Ethereum
+/// * account - hardened account index
+/// * change - 0 for external and 1 for internal chain
+/// * index - address index
+///
+pub fn bip44_path(coin_type: u32, account: u32, change: u32, index: u32) -> String {
+    format!("m/44'/{}'/{}'/{}/{}", coin_type, account, change, index)
+}
+
+/// Parses derivation path into child indexes, hardened ones offset by `HARDENED`
+///
+/// # Parameters
+///
+/// * path - derivation path starting with `m`
+///
+pub fn parse_path(path: &str) -> Result<Vec<u32>, Error> {
+    let mut segments = path.split('/');
+    if segments.next() != Some("m") {
+        return Err(Error::InvalidDerivationPath(path.to_string()));
+    }
+    segments
+        .map(|segment| {
+            let (index, offset) = match segment.strip_suffix(&['\'', 'h', 'H'][..]) {
+                Some(index) => (index, HARDENED),
+                None => (segment, 0),
+            };
+            match index.parse::<u32>() {
+                Ok(index) if index < HARDENED && !segment.starts_with('+') => Ok(index + offset),
+                _ => Err(Error::InvalidDerivationPath(path.to_string())),
+            }
+        })
+        .collect()
+}
+
+// HMAC-SHA512 of concatenated `data` parts, split into key and chain code halves,
+// parts are fed one by one so secret ones are never copied into a joint buffer
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> Result<ExtendedKey, Error> {
+    let mut mac =
+        Hmac::<Sha512>::new_from_slice(key).map_err(|e| Error::KdfError(e.to_string()))?;
+    for part in data {
+        mac.update(part);
+    }
+    let output = Zeroizing::new(mac.finalize().into_bytes().to_vec());
+    Ok(ExtendedKey {
+        key: Zeroizing::new(output[..32].to_vec()),
+        chain_code: Zeroizing::new(output[32..].to_vec()),
+    })
+}
+
+// parses secp256k1 scalar, failing for zero and values not below the curve order
+fn secp256k1_scalar(bytes: &[u8]) -> Result<Scalar, Error> {
+    Option::<Scalar>::from(Scalar::from_repr(*FieldBytes::from_slice(bytes)))
+        .filter(|scalar| !bool::from(scalar.is_zero()))
+        .ok_or_else(|| Error::KdfError("derived key is out of the curve order".into()))
+}
+
+// BIP32 master node of the seed
+fn bip32_master(seed: &[u8]) -> Result<ExtendedKey, Error> {
+    let node = hmac_sha512(b"Bitcoin seed", &[seed])?;
+    secp256k1_scalar(&node.key)?;
+    Ok(node)
+}
+
+// BIP32 private parent to private child derivation
+fn bip32_child(parent: &ExtendedKey, index: u32) -> Result<ExtendedKey, Error> {
+    let index_bytes = index.to_be_bytes();
+    let node = if index >= HARDENED {
+        hmac_sha512(&parent.chain_code, &[&[0u8], &parent.key, &index_bytes])?
+    } else {
+        let public_key = SigningKey::from_slice(&parent.key)?
+            .verifying_key()
+            .to_sec1_bytes();
+        hmac_sha512(&parent.chain_code, &[&public_key, &index_bytes])?
+    };
+    let child = secp256k1_scalar(&node.key)? + secp256k1_scalar(&parent.key)?;
+    if bool::from(child.is_zero()) {
+        return Err(Error::KdfError("derived key is zero".into()));
+    }
+    Ok(ExtendedKey {
+        key: Zeroizing::new(child.to_bytes().to_vec()),
+        chain_code: node.chain_code,
+    })
+}
+
+// SLIP-0010 hardened child derivation, child key is used as is
+fn slip10_hardened_child(parent: &ExtendedKey, index: u32) -> Result<ExtendedKey, Error> {
+    hmac_sha512(
+        &parent.chain_code,
+        &[&[0u8], &parent.key, &index.to_be_bytes()],
+    )
+}
+
+#[test]
+fn bip32_test_vector_1() -> Result<(), Error> {
+    // https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vector-1
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let cases = [
+        (
+            "m",
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35",
+        ),
+        (
+            "m/0'",
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+        ),
+        (
+            "m/0'/1",
+            "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368",
+        ),
+        (
+            "m/0h/1/2h",
+            "cbce0d719ecf7431d88e6a89fa1483e02e35092af60c042b1df2ff59fa424dca",
+        ),
+        (
+            "m/0H/1/2H/2",
+            "0f479245fb19a38a1954c5c7c0ebab2f9bdfd96a17563ef28a6a4b1a2a764ef4",
+        ),
+        (
+            "m/0'/1/2'/2/1000000000",
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8",
+        ),
+    ];
+    for (path, expected) in cases.iter() {
+        let key = derive_private_key(KeyType::EcdsaSecp256k1VerificationKey2019, &seed, path)?;
+        assert_eq!(hex::encode(&key[..]), *expected);
+    }
+    Ok(())
+}
+
+#[test]
+fn slip10_test_vector_1() -> Result<(), Error> {
+    // test vector 1 for ed25519 and curve25519 of
+    // https://github.com/satoshilabs/slips/blob/master/slip-0010.md
+    let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let cases = [
+        (
+            "m",
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            "d70a59c2e68b836cc4bbe8bcae425169b9e2384f3905091e3d60b890e90cd92c",
+        ),
+        (
+            "m/0'",
+            "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            "cd7630d7513cbe80515f7317cdb9a47ad4a56b63c3f1dc29583ab8d4cc25a9b2",
+        ),
+        (
+            "m/0'/1'",
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            "a95f97cfc1a61dd833b882c89d36a78a030ea6b2fbe3ae2a70e4f1fc9008d6b1",
+        ),
+        (
+            "m/0'/1'/2'",
+            "92a5b23c0b8a99e37d07df3fb9966917f5d06e02ddbd909c7e184371463e9fc9",
+            "3d6cce04a9175929da907a90b02176077b9ae050dcef9b959fed978bb2200cdc",
+        ),
+        (
+            "m/0'/1'/2'/2'",
+            "30d1dc7e5fc04c31219ab25a27ae00b50f6fd66622f6e9c913253d6511d1e662",
+            "7ae7437efe0a3018999e6f00d72e810ebc50578dbf6728bfa1c7fe73501081a7",
+        ),
+        (
+            "m/0'/1'/2'/2'/1000000000'",
+            "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            "7a59954d387abde3bc703f531f67d659ec2b8a12597ae82824547d7e27991e26",
+        ),
+    ];
+    for (path, ed25519, curve25519) in cases.iter() {
+        let key = derive_private_key(KeyType::Ed25519VerificationKey2018, &seed, path)?;
+        assert_eq!(hex::encode(&key[..]), *ed25519);
+        let key = derive_private_key(KeyType::X25519KeyAgreementKey2019, &seed, path)?;
+        assert_eq!(hex::encode(&key[..]), *curve25519);
+    }
+    assert!(matches!(
+        derive_private_key(KeyType::Ed25519VerificationKey2020, &seed, "m/0'/1"),
+        Err(Error::InvalidDerivationPath(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn derivation_path_parsing() -> Result<(), Error> {
+    assert_eq!(parse_path("m")?, Vec::<u32>::new());
+    assert_eq!(
+        parse_path("m/44'/60'/0'/0/7")?,
+        vec![HARDENED + 44, HARDENED + 60, HARDENED, 0, 7]
+    );
+    assert_eq!(
+        parse_path(&bip44_path(60, 0, 0, 7))?,
+        parse_path("m/44'/60'/0'/0/7")?
+    );
+    for invalid in ["", "44'/0", "m/", "m/x", "m/+1", "m/2147483648", "m/1''"].iter() {
+        assert!(matches!(
+            parse_path(invalid),
+            Err(Error::InvalidDerivationPath(_))
+        ));
+    }
+    assert!(matches!(
+        derive_private_key(KeyType::RsaVerificationKey2018, &[0u8; 16], "m"),
+        Err(Error::UnsupportedKeyType)
+    ));
+    Ok(())
+}
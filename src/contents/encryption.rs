@@ -1,11 +1,29 @@
 use crate::Error;
 use blake2::{Blake2b, Digest};
-use crypto_box::{aead::Aead, ChaChaBox, PublicKey, SecretKey};
-use generic_array::GenericArray;
+use chacha20::hchacha;
+use chacha20poly1305::{aead::Aead, KeyInit, XChaCha20Poly1305};
+use crypto_box::{PublicKey, SecretKey};
+use generic_array::{typenum::U10, GenericArray};
 use std::convert::TryInto;
+use zeroize::Zeroizing;
 
-pub fn make_channel(their_public: &PublicKey, our_secret: &SecretKey) -> ChaChaBox {
-    ChaChaBox::new(their_public, our_secret)
+/// Make Channel
+///
+/// Keeps the pre-0.9 `crypto_box` wire format: the X25519 shared secret
+/// is run through HChaCha20 with a zero input and used as an
+/// XChaCha20Poly1305 key, producing `ciphertext || tag`.
+///
+pub fn make_channel(their_public: &PublicKey, our_secret: &SecretKey) -> XChaCha20Poly1305 {
+    let shared = Zeroizing::new(
+        x25519_dalek::StaticSecret::from(our_secret.to_bytes())
+            .diffie_hellman(&x25519_dalek::PublicKey::from(*their_public.as_bytes()))
+            .to_bytes(),
+    );
+    let key = Zeroizing::new(hchacha::<U10>(
+        GenericArray::from_slice(&shared[..]),
+        &GenericArray::default(),
+    ));
+    XChaCha20Poly1305::new(&key)
 }
 /// Make Box
 ///
@@ -32,8 +50,8 @@ pub fn seal_box(data: &[u8], rk: &PublicKey) -> Result<Vec<u8>, Error> {
     let sk = SecretKey::generate(&mut rand::rngs::OsRng);
     let pk = PublicKey::from(&sk);
     let nonce = &Blake2b::new()
-        .chain(&pk.as_bytes())
-        .chain(&rk.as_bytes())
+        .chain(pk.as_bytes())
+        .chain(rk.as_bytes())
         .finalize()[..24];
     let b = make_box(data, rk, &sk, nonce)?;
     Ok([pk.as_bytes().to_vec(), b].concat())
@@ -81,8 +99,8 @@ pub fn unseal_box(data: &[u8], rsk: &SecretKey) -> Result<Vec<u8>, Error> {
             &epk,
             rsk,
             &Blake2b::new()
-                .chain(&epk.as_bytes())
-                .chain(&rpk.as_bytes())
+                .chain(epk.as_bytes())
+                .chain(rpk.as_bytes())
                 .finalize()[..24],
         )
     }
@@ -203,7 +221,7 @@ fn test_vector_salsa2() {
         0x56, 0x24, 0x4a, 0x9e, 0x88, 0xd5, 0xf9, 0xb3, 0x79, 0x73, 0xf6, 0x22, 0xa4, 0x3d, 0x14,
         0xa6, 0x59, 0x9b, 0x1f, 0x65, 0x4c, 0xb4, 0x5a, 0x74, 0xe3, 0x55, 0xa5,
     ];
-    let mexp: Result<Vec<u8>, Error> = Ok(vec![
+    let mexp = vec![
         0xbe, 0x07, 0x5f, 0xc5, 0x3c, 0x81, 0xf2, 0xd5, 0xcf, 0x14, 0x13, 0x16, 0xeb, 0xeb, 0x0c,
         0x7b, 0x52, 0x28, 0xc5, 0x2a, 0x4c, 0x62, 0xcb, 0xd4, 0x4b, 0x66, 0x84, 0x9b, 0x64, 0x24,
         0x4f, 0xfc, 0xe5, 0xec, 0xba, 0xaf, 0x33, 0xbd, 0x75, 0x1a, 0x1a, 0xc7, 0x28, 0xd4, 0x5e,
@@ -213,9 +231,9 @@ fn test_vector_salsa2() {
         0x83, 0x8f, 0x21, 0xaf, 0x1f, 0xde, 0x04, 0x89, 0x77, 0xeb, 0x48, 0xf5, 0x9f, 0xfd, 0x49,
         0x24, 0xca, 0x1c, 0x60, 0x90, 0x2e, 0x52, 0xf0, 0xa0, 0x89, 0xbc, 0x76, 0x89, 0x70, 0x40,
         0xe0, 0x82, 0xf9, 0x37, 0x76, 0x38, 0x48, 0x64, 0x5e, 0x07, 0x05,
-    ]);
+    ];
     let m = open_box(&c, &alicepk, &bobsk, &nonce);
-    assert_eq!(mexp.unwrap(), m.unwrap());
+    assert_eq!(mexp, m.unwrap());
 }
 #[test]
 fn test_vector_aead() {
@@ -1,41 +1,166 @@
-use serde::{
-    Deserialize,
-    Serialize
-};
+use crate::{get_random, Error};
+use bip39::Mnemonic;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroizing;
 
-/// Entropy type for JSON serialization
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Entropy type for JSON serialization.
+/// `Debug` output never contains the value.
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Entropy {
     #[serde(rename = "type")]
     pub entropy_type: Vec<String>,
     #[serde(with = "serde_base_64")]
-    value: Vec<u8>,
+    value: Zeroizing<Vec<u8>>,
+}
+
+/// `entropy_type` marker of BIP39 mnemonic entropy
+pub const BIP39_ENTROPY: &str = "Bip39Entropy";
+
+impl Entropy {
+    /// Wraps raw entropy, which is used as the seed as is
+    ///
+    /// # Parameters
+    ///
+    /// * value - secret bytes, e.g. BIP32 seed
+    ///
+    pub fn new(value: &[u8]) -> Self {
+        Self {
+            entropy_type: vec!["Entropy".to_string()],
+            value: Zeroizing::new(value.to_vec()),
+        }
+    }
+
+    /// Generates random entropy of
+    /// [BIP39](https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki) mnemonic
+    ///
+    /// # Parameters
+    ///
+    /// * word_count - number of mnemonic words, one of 12, 15, 18, 21 or 24
+    ///
+    pub fn random_mnemonic(word_count: usize) -> Result<Self, Error> {
+        if ![12, 15, 18, 21, 24].contains(&word_count) {
+            return Err(Error::MnemonicError(format!(
+                "unsupported word count {}",
+                word_count
+            )));
+        }
+        Ok(Self::bip39(Zeroizing::new(get_random(word_count / 3 * 4)?)))
+    }
+
+    /// Restores BIP39 entropy from the english mnemonic phrase
+    ///
+    /// # Parameters
+    ///
+    /// * phrase - space separated mnemonic words
+    ///
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, Error> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|e| Error::MnemonicError(e.to_string()))?;
+        Ok(Self::bip39(Zeroizing::new(mnemonic.to_entropy())))
+    }
+
+    /// Mnemonic phrase of BIP39 entropy
+    pub fn mnemonic(&self) -> Result<Zeroizing<String>, Error> {
+        Ok(Zeroizing::new(self.to_mnemonic()?.to_string()))
+    }
+
+    /// Whether this is BIP39 mnemonic entropy
+    pub fn is_mnemonic(&self) -> bool {
+        self.entropy_type.iter().any(|t| t == BIP39_ENTROPY)
+    }
+
+    // seed keys are derived from, BIP39 seed without passphrase for mnemonics
+    pub(crate) fn seed(&self) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if self.is_mnemonic() {
+            Ok(Zeroizing::new(self.to_mnemonic()?.to_seed("").to_vec()))
+        } else {
+            Ok(self.value.clone())
+        }
+    }
+
+    fn bip39(value: Zeroizing<Vec<u8>>) -> Self {
+        Self {
+            entropy_type: vec!["Entropy".to_string(), BIP39_ENTROPY.to_string()],
+            value,
+        }
+    }
+
+    fn to_mnemonic(&self) -> Result<Mnemonic, Error> {
+        if !self.is_mnemonic() {
+            return Err(Error::ContentTypeIncorrect);
+        }
+        Mnemonic::from_entropy(&self.value).map_err(|e| Error::MnemonicError(e.to_string()))
+    }
+}
+
+impl fmt::Debug for Entropy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entropy")
+            .field("entropy_type", &self.entropy_type)
+            .field("value", &"<redacted>")
+            .finish()
+    }
 }
 
 mod serde_base_64 {
     use base64;
     use serde::{de, Deserializer, Serializer};
-    pub fn serialize<S: Serializer>(t: &[u8], ser: S) -> Result<S::Ok, S::Error> {
-        ser.serialize_str(&base64::encode_config(t, base64::URL_SAFE))
+    use zeroize::Zeroizing;
+    pub fn serialize<S: Serializer>(t: &Zeroizing<Vec<u8>>, ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&Zeroizing::new(base64::encode_config(
+            &t[..],
+            base64::URL_SAFE,
+        )))
     }
-    pub fn deserialize<'a, D: Deserializer<'a>>(der: D) -> Result<Vec<u8>, D::Error> {
+    pub fn deserialize<'a, D: Deserializer<'a>>(der: D) -> Result<Zeroizing<Vec<u8>>, D::Error> {
         struct Base64Visitor;
 
         impl<'a> de::Visitor<'a> for Base64Visitor {
-            type Value = Vec<u8>;
+            type Value = Zeroizing<Vec<u8>>;
 
             fn expecting(&self, formatter: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
                 formatter.write_str("expected base64 encoded value")
             }
 
-            fn visit_str<E>(self, b64_encoded_str: &str) -> Result<Vec<u8>, E>
+            fn visit_str<E>(self, b64_encoded_str: &str) -> Result<Zeroizing<Vec<u8>>, E>
             where
                 E: serde::de::Error,
             {
-                base64::decode_config(b64_encoded_str, base64::URL_SAFE).map_err(de::Error::custom)
+                base64::decode_config(b64_encoded_str, base64::URL_SAFE)
+                    .map(Zeroizing::new)
+                    .map_err(de::Error::custom)
             }
         }
 
         der.deserialize_string(Base64Visitor)
     }
 }
+
+#[test]
+fn bip39_mnemonic_round_trip() -> Result<(), Error> {
+    // https://github.com/trezor/python-mnemonic/blob/master/vectors.json
+    let phrase = "abandon abandon abandon abandon abandon abandon \
+                  abandon abandon abandon abandon abandon about";
+    let entropy = Entropy::from_mnemonic(phrase)?;
+    assert!(entropy.is_mnemonic());
+    assert_eq!(&entropy.value[..], &[0u8; 16][..]);
+    assert_eq!(
+        hex::encode(&entropy.seed()?[..]),
+        "5eb00bbddcf069084889a8ab9155568165f5c453ccb85e70811aaed6f6da5fc1\
+         9a5ac40b389cd370d086206dec8aa6c43daea6690f20ad3d8d48b2d2ce9e38e4"
+    );
+
+    let json = serde_json::to_string(&entropy)?;
+    let parsed: Entropy = serde_json::from_str(&json)?;
+    assert_eq!(*parsed.mnemonic()?, phrase);
+
+    let random = Entropy::random_mnemonic(24)?;
+    assert_eq!(random.mnemonic()?.split(' ').count(), 24);
+    assert!(Entropy::random_mnemonic(13).is_err());
+    assert!(Entropy::from_mnemonic("abandon abandon abandon").is_err());
+    assert!(matches!(
+        Entropy::new(&[1u8; 32]).mnemonic(),
+        Err(Error::ContentTypeIncorrect)
+    ));
+    Ok(())
+}
@@ -1,23 +1,85 @@
+use super::bbs_plus;
+use super::bls;
+use super::derivation::derive_private_key;
 use super::encryption::unseal_box;
+use super::multicodec;
 use super::public_key_info::{KeyType, PublicKeyInfo};
 use crate::Error;
 use crypto_box::SecretKey;
-use ed25519_dalek::Keypair;
-use k256::ecdsa::{recoverable, signature::Signer, Signature, SigningKey};
-use rand_core::OsRng;
+use k256::{
+    ecdsa::{signature::Signer, Signature, SigningKey},
+    schnorr,
+};
+use rand_core::{OsRng, RngCore};
+use rsa::{
+    pkcs1::{DecodeRsaPrivateKey, EncodeRsaPrivateKey, EncodeRsaPublicKey},
+    pkcs1v15, pss,
+    signature::{RandomizedSigner, SignatureEncoding},
+    Oaep, RsaPrivateKey, RsaPublicKey,
+};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+use std::{convert::TryFrom, fmt};
 use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
 
 /// Serializable struct to hold pair of public and private keys.
 /// Universal for any key types as keys stored as bytes.
-#[derive(Serialize, Deserialize, Clone, Debug)]
+/// Private key is serialized in `encoding` format, which defaults to
+/// `privateKeyMultibase` for the types with `publicKeyMultibase` default
+/// and to `privateKeyHex` otherwise.
+/// `Debug` output never contains the private key.
+#[derive(Deserialize, Clone)]
+#[serde(try_from = "KeyPairRepr")]
 pub struct KeyPair {
     /// Public key is instance of `PublicKeyInfo` struct.
-    #[serde(flatten)]
     pub public_key: PublicKeyInfo,
-    /// Private key in form of vector of bytes.
-    #[serde(rename = "privateKeyHex", with = "hex")]
-    pub(crate) private_key: Vec<u8>,
+    /// Private key in form of vector of bytes, wiped from memory on drop.
+    /// Empty if the key is kept elsewhere, see `PrivateKeyFormat`.
+    pub(crate) private_key: Zeroizing<Vec<u8>>,
+    /// format private key is serialized in, `None` for the default one.
+    /// Set with `encoding`, read with `get_encoding`.
+    pub(crate) encoding: Option<PrivateKeyFormat>,
+}
+
+// JSON shape of `KeyPair`
+#[derive(Serialize, Deserialize)]
+struct KeyPairRepr {
+    #[serde(flatten)]
+    public_key: PublicKeyInfo,
+    #[serde(flatten)]
+    private_key: PrivateKeyEncoding,
+}
+
+impl TryFrom<KeyPairRepr> for KeyPair {
+    type Error = Error;
+
+    fn try_from(mut repr: KeyPairRepr) -> Result<Self, Self::Error> {
+        let key_pair = KeyPair::from_encoding(repr.public_key.clone(), &repr.private_key);
+        repr.private_key.zeroize();
+        key_pair
+    }
+}
+
+impl Serialize for KeyPair {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let default = match self.public_key.key_type {
+            KeyType::Ed25519VerificationKey2020
+            | KeyType::X25519KeyAgreementKey2020
+            | KeyType::Multikey => PrivateKeyFormat::Multibase,
+            _ => PrivateKeyFormat::Hex,
+        };
+        let mut repr = KeyPairRepr {
+            public_key: self.public_key.clone(),
+            private_key: self
+                .encode(self.encoding.as_ref().unwrap_or(&default))
+                .map_err(serde::ser::Error::custom)?,
+        };
+        let result = repr.serialize(ser);
+        repr.private_key.zeroize();
+        result
+    }
 }
 
 impl KeyPair {
@@ -31,23 +93,32 @@ impl KeyPair {
     /// `Ed25519VerificationKey2018` [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#ed25519)<br>
     /// `EcdsaSecp256k1VerificationKey2019` [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#ecdsasecp256k1signature2019)<br>
     /// `EcdsaSecp256k1RecoveryMethod2020` [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#ecdsasecp256k1recoverysignature2020)<br>
+    /// `JwsVerificationKey2020` with P-256 curve [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#jsonwebsignature2020)<br>
+    /// `EcdsaSecp384r1VerificationKey2019` and `EcdsaSecp521r1VerificationKey2019`
+    ///    NIST P-384 and P-521 curves [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#jsonwebsignature2020)<br>
+    /// `SchnorrSecp256k1VerificationKey2019` [BIP340](https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki)<br>
+    /// `RsaVerificationKey2018` PKCS#1 DER encoded [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#rsasignature2018)<br>
     //  TODO: find proper link for x25519 key
     /// `X25519KeyAgreementKey2019` [W3C](https://www.w3.org/TR/did-core/#key-types-and-formats)<br>
+    /// `Bls12381G1Key2020` and `Bls12381G2Key2020` [W3C](https://w3c-ccg.github.io/ldp-bbs2020/)<br>
     ///
     pub fn new(key_type: KeyType, priv_key: &[u8]) -> Result<Self, Error> {
         let (pk, sk) = match key_type {
-            KeyType::Ed25519VerificationKey2018 => {
-                let sk =
-                    ed25519_dalek::SecretKey::from_bytes(priv_key).map_err(Error::EdCryptoError)?;
-                let pk: ed25519_dalek::PublicKey = (&sk).into();
-                (pk.as_bytes().to_vec(), sk.as_bytes().to_vec())
+            KeyType::Ed25519VerificationKey2018 | KeyType::Ed25519VerificationKey2020 => {
+                let sk = ed25519_dalek::SigningKey::from_bytes(
+                    <&[u8; 32]>::try_from(priv_key).map_err(|_| Error::WrongKeyLength)?,
+                );
+                (
+                    sk.verifying_key().as_bytes().to_vec(),
+                    sk.as_bytes().to_vec(),
+                )
             }
             KeyType::EcdsaSecp256k1VerificationKey2019
             | KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
-                let sign_key = SigningKey::from_bytes(priv_key)?;
+                let sign_key = SigningKey::from_slice(priv_key)?;
                 let verify_key = sign_key.verifying_key();
                 (
-                    verify_key.to_bytes().to_vec(),
+                    verify_key.to_sec1_bytes().to_vec(),
                     sign_key
                         .to_bytes()
                         .iter_mut()
@@ -55,27 +126,86 @@ impl KeyPair {
                         .collect::<Vec<u8>>(),
                 )
             }
-            KeyType::X25519KeyAgreementKey2019 => {
-                let secret = StaticSecret::from(array_ref!(priv_key, 0, 32).to_owned());
+            KeyType::JwsVerificationKey2020 => {
+                let sign_key = p256::ecdsa::SigningKey::from_slice(priv_key)?;
+                (
+                    sign_key
+                        .verifying_key()
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::EcdsaSecp384r1VerificationKey2019 => {
+                let sign_key = p384::ecdsa::SigningKey::from_slice(priv_key)?;
+                (
+                    sign_key
+                        .verifying_key()
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::EcdsaSecp521r1VerificationKey2019 => {
+                let sign_key = p521::ecdsa::SigningKey::from_slice(priv_key)
+                    .map_err(Error::P521CryptoError)?;
+                (
+                    p521::ecdsa::VerifyingKey::from(&sign_key)
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                // BIP340 keys are stored as given, even if their point has odd Y
+                let sign_key = schnorr::SigningKey::from_bytes(priv_key)?;
+                (
+                    sign_key.verifying_key().to_bytes().to_vec(),
+                    priv_key.to_vec(),
+                )
+            }
+            KeyType::RsaVerificationKey2018 => {
+                let sk = rsa_private_key(priv_key)?;
+                let pk = RsaPublicKey::from(&sk)
+                    .to_pkcs1_der()
+                    .map_err(rsa::errors::Error::from)?;
+                (pk.as_bytes().to_vec(), priv_key.to_vec())
+            }
+            KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+                let secret = StaticSecret::from(
+                    <[u8; 32]>::try_from(priv_key).map_err(|_| Error::WrongKeyLength)?,
+                );
                 let pk = PublicKey::from(&secret).to_bytes();
                 (pk.to_vec(), secret.to_bytes().to_vec())
             }
+            KeyType::Bls12381G1Key2020 => {
+                let sk = bls::secret_key(priv_key)?;
+                let pk = signature_bls::PublicKeyVt::from(&sk);
+                (pk.to_bytes().to_vec(), sk.to_bytes().to_vec())
+            }
+            KeyType::Bls12381G2Key2020 => {
+                let sk = bls::secret_key(priv_key)?;
+                let pk = signature_bls::PublicKey::from(&sk);
+                (pk.to_bytes().to_vec(), sk.to_bytes().to_vec())
+            }
             _ => return Err(Error::UnsupportedKeyType),
         };
 
         Ok(KeyPair {
-            public_key: PublicKeyInfo {
-                controller: vec![],
-                key_type,
-                public_key: pk,
-            },
-            private_key: sk,
+            public_key: PublicKeyInfo::new(key_type, &pk),
+            private_key: sk.into(),
+            encoding: None,
         })
     }
 
     /// Generates random `KeyPair` of specified `KeyType` type.
     ///
     /// *`key_type` - variont of `KeyType` enum.
+    /// `RsaVerificationKey2018` keys are 2048 bit long, use
+    /// `random_rsa_pair` for the longer ones.
     ///
     /// # Examples
     ///
@@ -97,25 +227,68 @@ impl KeyPair {
     ///
     pub fn random_pair(key_type: KeyType) -> Result<KeyPair, Error> {
         let (pk, sk) = match key_type {
-            KeyType::X25519KeyAgreementKey2019 => {
-                let sk = StaticSecret::new(OsRng);
+            KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+                let sk = StaticSecret::random_from_rng(OsRng);
                 (
                     PublicKey::from(&sk).as_bytes().to_vec(),
                     sk.to_bytes().to_vec(),
                 )
             }
-            KeyType::Ed25519VerificationKey2018 => {
-                let kp = Keypair::generate(&mut OsRng);
-                (kp.public.as_bytes().to_vec(), kp.secret.as_bytes().to_vec())
+            KeyType::Ed25519VerificationKey2018 | KeyType::Ed25519VerificationKey2020 => {
+                let sk = ed25519_dalek::SigningKey::generate(&mut OsRng);
+                (
+                    sk.verifying_key().as_bytes().to_vec(),
+                    sk.as_bytes().to_vec(),
+                )
             }
             KeyType::EcdsaSecp256k1VerificationKey2019
             | KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
-                let sign_key = SigningKey::random(&mut rand::rngs::OsRng);
+                let sign_key = SigningKey::random(&mut OsRng);
+                (
+                    sign_key.verifying_key().to_sec1_bytes().to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::JwsVerificationKey2020 => {
+                let sign_key = p256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+                (
+                    sign_key
+                        .verifying_key()
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::EcdsaSecp384r1VerificationKey2019 => {
+                let sign_key = p384::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+                (
+                    sign_key
+                        .verifying_key()
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::EcdsaSecp521r1VerificationKey2019 => {
+                let sign_key = p521::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+                (
+                    p521::ecdsa::VerifyingKey::from(&sign_key)
+                        .to_encoded_point(true)
+                        .as_bytes()
+                        .to_vec(),
+                    sign_key.to_bytes().to_vec(),
+                )
+            }
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                let sign_key = schnorr::SigningKey::random(&mut rand::rngs::OsRng);
                 (
                     sign_key.verifying_key().to_bytes().to_vec(),
                     sign_key.to_bytes().to_vec(),
                 )
             }
+            KeyType::RsaVerificationKey2018 => return KeyPair::random_rsa_pair(2048),
             KeyType::Bls12381G1Key2020 => {
                 use signature_bls::{PublicKeyVt, SecretKey};
                 let sk = SecretKey::random(&mut rand::rngs::OsRng)
@@ -134,15 +307,98 @@ impl KeyPair {
         };
 
         Ok(KeyPair {
-            public_key: PublicKeyInfo {
-                controller: vec![],
-                key_type,
-                public_key: pk,
-            },
-            private_key: sk,
+            public_key: PublicKeyInfo::new(key_type, &pk),
+            private_key: sk.into(),
+            encoding: None,
         })
     }
 
+    /// Decodes private key from any `PrivateKeyEncoding`.
+    /// Keys kept in KMS, secure enclave or derived from the seed are
+    /// left empty and can not be used until resolved.
+    /// Encoding is kept, so key is serialized the way it was received.
+    ///
+    /// # Parameters
+    ///
+    /// * public_key - `PublicKeyInfo` of the key pair
+    /// * encoded - private key in one of supported encodings
+    ///
+    pub fn from_encoding(
+        public_key: PublicKeyInfo,
+        encoded: &PrivateKeyEncoding,
+    ) -> Result<Self, Error> {
+        let private_key = match encoded {
+            PrivateKeyEncoding::PrivateKeyHex(sk) => hex::decode(sk)
+                .map(Zeroizing::new)
+                .map_err(|_| Error::InvalidPrivateKey)?,
+            PrivateKeyEncoding::PrivateKeyBase64(sk) => base64::decode_config(sk, base64::URL_SAFE)
+                .map(Zeroizing::new)
+                .map_err(|_| Error::InvalidPrivateKey)?,
+            PrivateKeyEncoding::PrivateKeyBase58(sk) => bs58::decode(sk)
+                .into_vec()
+                .map(Zeroizing::new)
+                .map_err(|_| Error::InvalidPrivateKey)?,
+            PrivateKeyEncoding::PrivateKeyMultibase(sk) => {
+                multicodec::decode_private_key(concrete_type(&public_key)?, sk)?
+            }
+            PrivateKeyEncoding::PrivateKeyWebKms(_)
+            | PrivateKeyEncoding::PrivateKeySecureEnclave(_)
+            | PrivateKeyEncoding::PrivateKeyFromSeed { .. } => Zeroizing::new(vec![]),
+        };
+        Ok(KeyPair {
+            public_key,
+            private_key,
+            encoding: Some(encoded.format()),
+        })
+    }
+
+    /// Derives `KeyPair` of specified `KeyType` from the seed.
+    /// See `derivation::derive_private_key` for supported key types.
+    ///
+    /// # Parameters
+    ///
+    /// * key_type - variant of `KeyType` enum
+    /// * seed - master seed
+    /// * path - derivation path like `m/44'/60'/0'/0/0`
+    ///
+    pub fn derive(key_type: KeyType, seed: &[u8], path: &str) -> Result<Self, Error> {
+        KeyPair::new(key_type, &derive_private_key(key_type, seed, path)?)
+    }
+
+    /// Converts Ed25519 key pair into birationally equivalent X25519 one,
+    /// so the same identity can be used for key agreement and decryption.
+    /// X25519 private key is the clamped scalar of the Ed25519 signing key.
+    /// `Ed25519VerificationKey2018` converts to `X25519KeyAgreementKey2019`,
+    /// `Ed25519VerificationKey2020` and Ed25519 `Multikey` to `X25519KeyAgreementKey2020`.
+    /// Controller is kept.
+    ///
+    pub fn to_x25519(&self) -> Result<KeyPair, Error> {
+        self.check_private_key()?;
+        let key_type = match concrete_type(&self.public_key)? {
+            KeyType::Ed25519VerificationKey2018 => KeyType::X25519KeyAgreementKey2019,
+            KeyType::Ed25519VerificationKey2020 => KeyType::X25519KeyAgreementKey2020,
+            _ => return Err(Error::WrongKeyType),
+        };
+        let sk = ed25519_dalek::SigningKey::from_bytes(
+            <&[u8; 32]>::try_from(&self.private_key[..]).map_err(|_| Error::WrongKeyLength)?,
+        );
+        let scalar = Zeroizing::new(sk.to_scalar_bytes());
+        Ok(KeyPair::new(key_type, &scalar[..])?.set_controller(self.public_key.controller.clone()))
+    }
+
+    /// Generates random `KeyPair` of `RsaVerificationKey2018` type.
+    ///
+    /// *`bits` - modulus size, one of 2048, 3072 or 4096.
+    ///
+    pub fn random_rsa_pair(bits: usize) -> Result<KeyPair, Error> {
+        if ![2048, 3072, 4096].contains(&bits) {
+            return Err(Error::WrongKeyLength);
+        }
+        let sk = RsaPrivateKey::new(&mut rand::rngs::OsRng, bits)?;
+        let der = sk.to_pkcs1_der().map_err(rsa::errors::Error::from)?;
+        KeyPair::new(KeyType::RsaVerificationKey2018, der.as_bytes())
+    }
+
     /// Updates `.controller` property to provided value.
     ///
     /// *`controller` - collection of controller `String`s.
@@ -154,44 +410,136 @@ impl KeyPair {
         }
     }
 
+    /// Encodes private key into `format` provided.
+    /// Material bearing encodings fail for keys which are kept elsewhere.
+    /// Returned encoding holds the secret, zeroize it once used.
+    ///
+    /// # Parameters
+    ///
+    /// * format - desired `PrivateKeyFormat`
+    ///
+    pub fn encode(&self, format: &PrivateKeyFormat) -> Result<PrivateKeyEncoding, Error> {
+        let sk = &self.private_key[..];
+        Ok(match format {
+            PrivateKeyFormat::WebKms(id) => PrivateKeyEncoding::PrivateKeyWebKms(id.clone()),
+            PrivateKeyFormat::SecureEnclave(id) => {
+                PrivateKeyEncoding::PrivateKeySecureEnclave(id.clone())
+            }
+            PrivateKeyFormat::FromSeed { path, seed_ref } => {
+                PrivateKeyEncoding::PrivateKeyFromSeed {
+                    path: path.clone(),
+                    seed_ref: seed_ref.clone(),
+                }
+            }
+            _ if sk.is_empty() => return Err(Error::PrivateKeyUnavailable),
+            PrivateKeyFormat::Hex => PrivateKeyEncoding::PrivateKeyHex(hex::encode(sk)),
+            PrivateKeyFormat::Base64 => {
+                PrivateKeyEncoding::PrivateKeyBase64(base64::encode_config(sk, base64::URL_SAFE))
+            }
+            PrivateKeyFormat::Base58 => {
+                PrivateKeyEncoding::PrivateKeyBase58(bs58::encode(sk).into_string())
+            }
+            PrivateKeyFormat::Multibase => PrivateKeyEncoding::PrivateKeyMultibase(
+                multicodec::encode_private_key(concrete_type(&self.public_key)?, sk)?,
+            ),
+        })
+    }
+
+    /// Sets format private key is serialized in and returns updated struct.
+    ///
+    /// *`format` - `PrivateKeyFormat` to be used for serialization.
+    ///
+    pub fn encoding(self, format: PrivateKeyFormat) -> Self {
+        KeyPair {
+            encoding: Some(format),
+            ..self
+        }
+    }
+
+    /// Returns format private key is serialized in, `None` for the default one.
+    pub fn get_encoding(&self) -> Option<&PrivateKeyFormat> {
+        self.encoding.as_ref()
+    }
+
     /// Signst provided message with the key from `KeyType` set during `KeyPair` creation.
     ///
-    /// *`data` - message slice to be signed
+    /// *`data` - message slice to be signed.
+    /// `SchnorrSecp256k1VerificationKey2019` signs SHA-256 hash of `data`,
+    /// see `sign_prehashed` for signing hashes as is.
+    /// `RsaVerificationKey2018` produces RS256 signatures, see `sign_pss` for PS256.
     ///
     /// Returns `Result` of generated signature in form of `Vec<u8>` or `Error`.
     ///
     pub fn sign(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
         match self.public_key.key_type {
-            KeyType::Ed25519VerificationKey2018 => {
-                let mut pk = self.public_key.public_key.clone();
-                let mut spk = self.private_key.clone();
-                spk.append(&mut pk);
-                let kp = Keypair::from_bytes(spk.as_ref()).map_err(Error::EdCryptoError)?;
+            KeyType::Multikey => self.resolve_multikey()?.sign(data),
+            KeyType::Ed25519VerificationKey2018 | KeyType::Ed25519VerificationKey2020 => {
+                // secret and public halves are joined in place, never reallocated
+                if self.private_key.len() != 32 || self.public_key.public_key.len() != 32 {
+                    return Err(Error::WrongKeyLength);
+                }
+                let mut spk = Zeroizing::new([0u8; 64]);
+                spk[..32].copy_from_slice(&self.private_key);
+                spk[32..].copy_from_slice(&self.public_key.public_key);
+                let kp = ed25519_dalek::SigningKey::from_keypair_bytes(&spk)
+                    .map_err(Error::EdCryptoError)?;
                 let sig = kp.sign(data);
                 Ok(sig.to_bytes().into())
             }
             KeyType::EcdsaSecp256k1VerificationKey2019 => {
-                let sign_key = SigningKey::from_bytes(&self.private_key)?;
+                let sign_key = SigningKey::from_slice(&self.private_key)?;
                 let signature: Signature = sign_key.sign(data);
-                Ok(signature.as_ref().to_vec())
+                Ok(signature.to_vec())
             }
             KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
-                let sign_key = SigningKey::from_bytes(&self.private_key[..])?;
-                // WARN: Signature type must be annotated to be recoverable!
-                let signature: recoverable::Signature = sign_key.sign(data);
-                Ok(signature.as_ref().to_vec())
+                let sign_key = SigningKey::from_slice(&self.private_key)?;
+                // Ethereum style, message is hashed with Keccak256 and
+                // recovery id is appended to r and s
+                let (signature, recovery_id) =
+                    sign_key.sign_prehash_recoverable(&Keccak256::digest(data))?;
+                let mut signature = signature.to_vec();
+                signature.push(recovery_id.to_byte());
+                Ok(signature)
+            }
+            KeyType::JwsVerificationKey2020 => {
+                let sign_key = p256::ecdsa::SigningKey::from_slice(&self.private_key)?;
+                let signature: p256::ecdsa::Signature = sign_key.sign(data);
+                Ok(signature.to_vec())
+            }
+            KeyType::EcdsaSecp384r1VerificationKey2019 => {
+                let sign_key = p384::ecdsa::SigningKey::from_slice(&self.private_key)?;
+                let signature: p384::ecdsa::Signature = sign_key.sign(data);
+                Ok(signature.to_vec())
+            }
+            KeyType::EcdsaSecp521r1VerificationKey2019 => {
+                let sign_key = p521::ecdsa::SigningKey::from_slice(&self.private_key)
+                    .map_err(Error::P521CryptoError)?;
+                let signature: p521::ecdsa::Signature = sign_key.sign(data);
+                Ok(signature.to_vec())
+            }
+            KeyType::RsaVerificationKey2018 => {
+                let sign_key =
+                    pkcs1v15::SigningKey::<Sha256>::new(rsa_private_key(&self.private_key)?);
+                let signature = sign_key
+                    .try_sign_with_rng(&mut rand::rngs::OsRng, data)
+                    .map_err(|e| Error::Other(Box::new(e)))?;
+                Ok(signature.to_vec())
+            }
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                let mut aux = [0u8; 32];
+                OsRng.fill_bytes(&mut aux);
+                self.sign_prehashed(&Sha256::digest(data), &aux)
             }
             KeyType::Bls12381G2Key2020 => {
-                use signature_bls::{SecretKey, Signature};
-                let sk = SecretKey::from_bytes(array_ref!(&self.private_key, 0, 32)).unwrap();
-                let sig = Signature::new(&sk, data)
+                let sk = bls::secret_key(&self.private_key)?;
+                let sig = signature_bls::Signature::new(&sk, data)
                     .ok_or_else(|| Error::BlsCryptoError("payload signing failed".into()))?;
                 Ok(sig.to_bytes().to_vec())
             }
             KeyType::Bls12381G1Key2020 => {
-                use signature_bls::{SecretKey, SignatureVt};
-                let sk = SecretKey::from_bytes(array_ref!(&self.private_key, 0, 32)).unwrap();
-                let sig = SignatureVt::new(&sk, data)
+                let sk = bls::secret_key(&self.private_key)?;
+                let sig = signature_bls::SignatureVt::new(&sk, data)
                     .ok_or_else(|| Error::BlsCryptoError("payload signing failed".into()))?;
                 Ok(sig.to_bytes().to_vec())
             }
@@ -199,6 +547,98 @@ impl KeyPair {
         }
     }
 
+    /// Signs 32 byte message hash as is with BIP340 Schnorr signature.
+    /// Supported by `SchnorrSecp256k1VerificationKey2019` keys only.
+    ///
+    /// # Parameters
+    ///
+    /// * hash - 32 byte hash of the message
+    /// * aux_rand - auxiliary randomness, `sign` uses random bytes
+    ///
+    /// Returns `Result` of signature checked by `PublicKeyInfo::verify_prehashed`.
+    ///
+    pub fn sign_prehashed(&self, hash: &[u8], aux_rand: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
+        match self.public_key.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.sign_prehashed(hash, aux_rand),
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                let hash = <&[u8; 32]>::try_from(hash).map_err(|_| Error::WrongMessageLength)?;
+                let sign_key = schnorr::SigningKey::from_bytes(&self.private_key)?;
+                let signature = sign_key.sign_prehash_with_aux_rand(hash, aux_rand)?;
+                Ok(signature.to_bytes().to_vec())
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Signs provided message with RSASSA-PSS using SHA-256 (PS256).
+    /// Supported by `RsaVerificationKey2018` keys only,
+    /// signatures are checked by `PublicKeyInfo::verify_pss`.
+    ///
+    /// *`data` - message slice to be signed
+    ///
+    pub fn sign_pss(&self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
+        match self.public_key.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.sign_pss(data),
+            KeyType::RsaVerificationKey2018 => {
+                // salt is as long as the digest, private key operation is blinded
+                let sign_key =
+                    pss::BlindedSigningKey::<Sha256>::new(rsa_private_key(&self.private_key)?);
+                let signature = sign_key
+                    .try_sign_with_rng(&mut rand::rngs::OsRng, data)
+                    .map_err(|e| Error::Other(Box::new(e)))?;
+                Ok(signature.to_vec())
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Signs list of messages as one BBS+ signature, from which holder
+    /// derives selective disclosure proofs with `PublicKeyInfo::derive_bbs_proof`.
+    /// Supported by `Bls12381G2Key2020` keys only.
+    ///
+    /// *`messages` - messages to be signed, in the order they are disclosed by index
+    ///
+    pub fn sign_bbs<M: AsRef<[u8]>>(&self, messages: &[M]) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
+        match self.public_key.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.sign_bbs(messages),
+            KeyType::Bls12381G2Key2020 => {
+                bbs_plus::sign(&self.private_key, &self.public_key.public_key, messages)
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Generates proof of possession of BLS private key, which guards
+    /// aggregation over the same message against rogue key attacks.
+    /// Supported by `Bls12381G1Key2020` and `Bls12381G2Key2020` keys only.
+    ///
+    /// Returns `Result` of the proof checked by `PublicKeyInfo::verify_possession`.
+    ///
+    pub fn prove_possession(&self) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
+        match self.public_key.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.prove_possession(),
+            KeyType::Bls12381G1Key2020 => {
+                let sk = bls::secret_key(&self.private_key)?;
+                let proof = signature_bls::ProofOfPossessionVt::new(&sk).ok_or_else(|| {
+                    Error::BlsCryptoError("proof of possession generation failed".into())
+                })?;
+                Ok(proof.to_bytes().to_vec())
+            }
+            KeyType::Bls12381G2Key2020 => {
+                let sk = bls::secret_key(&self.private_key)?;
+                let proof = signature_bls::ProofOfPossession::new(&sk).ok_or_else(|| {
+                    Error::BlsCryptoError("proof of possession generation failed".into())
+                })?;
+                Ok(proof.to_bytes().to_vec())
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
     /// Decrypts cipher data using current private key.
     ///
     /// *`data` - cipher to be derypted.
@@ -207,41 +647,148 @@ impl KeyPair {
     ///     `Error` indication failure.
     ///
     pub fn decrypt(&self, data: &[u8], _aad: Option<&[u8]>) -> Result<Vec<u8>, Error> {
+        self.check_private_key()?;
         match self.public_key.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.decrypt(data, _aad),
             // default use xChaCha20Poly1905 with x25519 key agreement
-            KeyType::X25519KeyAgreementKey2019 => unseal_box(
-                data,
-                &SecretKey::from(array_ref!(&self.private_key, 0, 32).to_owned()),
-            ),
+            KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+                let sk = <[u8; 32]>::try_from(&self.private_key[..])
+                    .map_err(|_| Error::WrongKeyLength)?;
+                unseal_box(data, &SecretKey::from(sk))
+            }
+            // RSA-OAEP with SHA-256
+            KeyType::RsaVerificationKey2018 => Ok(rsa_private_key(&self.private_key)?
+                .decrypt_blinded(&mut rand::rngs::OsRng, Oaep::new::<Sha256>(), data)?),
             _ => Err(Error::WrongKeyType),
         }
     }
 
     /// Performs ECDH key agreement
     ///
-    /// *`pk` - public key to perform agreement with
+    /// *`pk` - public key to perform agreement with,
+    ///    SEC1 encoded for NIST curves and secp256k1, 32 bytes for X25519.
+    ///    Malformed and low order X25519 keys fail with `Error::InvalidPublicKey`
     ///
-    /// Returns `Result` of shared secret in `Vec<u8>` form`
-    pub fn ecdh_key_agreement(&self, pk: &[u8]) -> Result<Vec<u8>, Error> {
+    /// Returns `Result` of shared secret in `Vec<u8>` form, wiped from memory on drop,
+    ///    x-coordinate of the shared point for elliptic curves
+    pub fn ecdh_key_agreement(&self, pk: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        self.check_private_key()?;
         match self.public_key.key_type {
-            KeyType::X25519KeyAgreementKey2019 => Ok(StaticSecret::from(
-                array_ref!(&self.private_key, 0, 32).to_owned(),
-            )
-            .diffie_hellman(&PublicKey::from(array_ref!(pk, 0, 32).to_owned()))
-            .to_bytes()
-            .into()),
+            KeyType::Multikey => self.resolve_multikey()?.ecdh_key_agreement(pk),
+            KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+                let sk = Zeroizing::new(
+                    <[u8; 32]>::try_from(&self.private_key[..])
+                        .map_err(|_| Error::WrongKeyLength)?,
+                );
+                let peer = <[u8; 32]>::try_from(pk).map_err(|_| Error::InvalidPublicKey)?;
+                let shared = Zeroizing::new(
+                    StaticSecret::from(*sk)
+                        .diffie_hellman(&PublicKey::from(peer))
+                        .to_bytes(),
+                );
+                // low order peer keys produce all zero secret
+                if shared.iter().all(|b| *b == 0) {
+                    return Err(Error::InvalidPublicKey);
+                }
+                Ok(Zeroizing::new(shared.to_vec()))
+            }
+            // shared secret is the x-coordinate of the shared point
+            KeyType::EcdsaSecp256k1VerificationKey2019
+            | KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
+                let sign_key = SigningKey::from_slice(&self.private_key)?;
+                let peer =
+                    k256::PublicKey::from_sec1_bytes(pk).map_err(|_| Error::InvalidPublicKey)?;
+                let shared =
+                    k256::ecdh::diffie_hellman(sign_key.as_nonzero_scalar(), peer.as_affine());
+                Ok(Zeroizing::new(shared.raw_secret_bytes().to_vec()))
+            }
+            KeyType::JwsVerificationKey2020 => {
+                let sign_key = p256::ecdsa::SigningKey::from_slice(&self.private_key)?;
+                let peer =
+                    p256::PublicKey::from_sec1_bytes(pk).map_err(|_| Error::InvalidPublicKey)?;
+                let shared =
+                    p256::ecdh::diffie_hellman(sign_key.as_nonzero_scalar(), peer.as_affine());
+                Ok(Zeroizing::new(shared.raw_secret_bytes().to_vec()))
+            }
+            KeyType::EcdsaSecp384r1VerificationKey2019 => {
+                let sign_key = p384::ecdsa::SigningKey::from_slice(&self.private_key)?;
+                let peer =
+                    p384::PublicKey::from_sec1_bytes(pk).map_err(|_| Error::InvalidPublicKey)?;
+                let shared =
+                    p384::ecdh::diffie_hellman(sign_key.as_nonzero_scalar(), peer.as_affine());
+                Ok(Zeroizing::new(shared.raw_secret_bytes().to_vec()))
+            }
+            KeyType::EcdsaSecp521r1VerificationKey2019 => {
+                let sign_key = p521::ecdsa::SigningKey::from_slice(&self.private_key)
+                    .map_err(Error::P521CryptoError)?;
+                let peer =
+                    p521::PublicKey::from_sec1_bytes(pk).map_err(|_| Error::InvalidPublicKey)?;
+                let shared =
+                    p521::ecdh::diffie_hellman(sign_key.as_nonzero_scalar(), peer.as_affine());
+                Ok(Zeroizing::new(shared.raw_secret_bytes().to_vec()))
+            }
             _ => Err(Error::WrongKeyType),
         }
     }
 
+    /// Converts own public key into `Multikey`, see `PublicKeyInfo::to_multikey`.
+    pub fn to_multikey(&self) -> Result<Self, Error> {
+        Ok(KeyPair {
+            public_key: self.public_key.to_multikey()?,
+            private_key: self.private_key.clone(),
+            encoding: self.encoding.clone(),
+        })
+    }
+
+    // resolves `Multikey` into key pair of concrete type
+    fn resolve_multikey(&self) -> Result<Self, Error> {
+        Ok(KeyPair {
+            public_key: self.public_key.resolve_multikey()?,
+            private_key: self.private_key.clone(),
+            encoding: self.encoding.clone(),
+        })
+    }
+
     /// Returns `Clone`d instance of `PublicKeyInfo` from own `public_key` property.
     pub fn get_public_key(&self) -> PublicKeyInfo {
         self.public_key.clone()
     }
 
-    /// Returns `Clone`d instance of `Vec<u8>` from own private key.
-    pub fn private_key(&self) -> Vec<u8> {
-        self.private_key.clone()
+    /// Lends own private key without copying it.
+    /// Copy it into `Zeroizing` buffer if it must outlive the `KeyPair`.
+    /// Empty for keys kept elsewhere.
+    pub fn private_key(&self) -> &[u8] {
+        &self.private_key
+    }
+
+    // fails for keys which private key is kept outside of the `KeyPair`
+    fn check_private_key(&self) -> Result<(), Error> {
+        if self.private_key.is_empty() {
+            return Err(Error::PrivateKeyUnavailable);
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public_key", &self.public_key)
+            .field("private_key", &"<redacted>")
+            .finish()
+    }
+}
+
+// parses PKCS#1 DER encoded RSA private key
+fn rsa_private_key(der: &[u8]) -> Result<RsaPrivateKey, Error> {
+    Ok(RsaPrivateKey::from_pkcs1_der(der).map_err(rsa::errors::Error::from)?)
+}
+
+// concrete type of the key, `Multikey`s are resolved by their prefix
+fn concrete_type(public_key: &PublicKeyInfo) -> Result<KeyType, Error> {
+    match public_key.key_type {
+        KeyType::Multikey => Ok(public_key.resolve_multikey()?.key_type),
+        key_type => Ok(key_type),
     }
 }
 
@@ -256,7 +803,74 @@ pub enum PrivateKeyEncoding {
     PrivateKeyMultibase(String),
     PrivateKeyWebKms(String),
     PrivateKeySecureEnclave(String),
-    PrivateKeyFromSeed { path: String, seed_ref: String },
+    /// Key derived from `Entropy` with id `seed_ref` along `path`
+    #[serde(rename_all = "camelCase")]
+    PrivateKeyFromSeed {
+        path: String,
+        seed_ref: String,
+    },
+}
+
+impl PrivateKeyEncoding {
+    // format of this encoding
+    fn format(&self) -> PrivateKeyFormat {
+        match self {
+            PrivateKeyEncoding::PrivateKeyHex(_) => PrivateKeyFormat::Hex,
+            PrivateKeyEncoding::PrivateKeyBase64(_) => PrivateKeyFormat::Base64,
+            PrivateKeyEncoding::PrivateKeyBase58(_) => PrivateKeyFormat::Base58,
+            PrivateKeyEncoding::PrivateKeyMultibase(_) => PrivateKeyFormat::Multibase,
+            PrivateKeyEncoding::PrivateKeyWebKms(id) => PrivateKeyFormat::WebKms(id.clone()),
+            PrivateKeyEncoding::PrivateKeySecureEnclave(id) => {
+                PrivateKeyFormat::SecureEnclave(id.clone())
+            }
+            PrivateKeyEncoding::PrivateKeyFromSeed { path, seed_ref } => {
+                PrivateKeyFormat::FromSeed {
+                    path: path.clone(),
+                    seed_ref: seed_ref.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// Wipes encoded private key, references to keys kept elsewhere are wiped as well
+impl Zeroize for PrivateKeyEncoding {
+    fn zeroize(&mut self) {
+        match self {
+            PrivateKeyEncoding::PrivateKeyHex(sk)
+            | PrivateKeyEncoding::PrivateKeyBase64(sk)
+            | PrivateKeyEncoding::PrivateKeyBase58(sk)
+            | PrivateKeyEncoding::PrivateKeyMultibase(sk)
+            | PrivateKeyEncoding::PrivateKeyWebKms(sk)
+            | PrivateKeyEncoding::PrivateKeySecureEnclave(sk) => sk.zeroize(),
+            PrivateKeyEncoding::PrivateKeyFromSeed { path, seed_ref } => {
+                path.zeroize();
+                seed_ref.zeroize();
+            }
+        }
+    }
+}
+
+/// Selects `PrivateKeyEncoding` private key is encoded into
+/// or where the key is kept instead.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PrivateKeyFormat {
+    Hex,
+    /// URL safe base64
+    Base64,
+    Base58,
+    /// base58btc multibase of multicodec prefixed key
+    Multibase,
+    /// Key kept in Web KMS under the id provided
+    WebKms(String),
+    /// Key kept in secure enclave under the id provided
+    SecureEnclave(String),
+    /// Key derived from `Entropy` content with id `seed_ref` along `path`,
+    /// resolved by `UnlockedWallet`
+    FromSeed {
+        path: String,
+        seed_ref: String,
+    },
 }
 
 #[test]
@@ -282,9 +896,18 @@ fn key_pair_new_ed25519() {
     )
 }
 
+#[test]
+fn debug_redacts_private_key() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::Ed25519VerificationKey2018)?;
+    let debug = format!("{:?}", kp);
+    assert!(debug.contains("<redacted>"));
+    assert!(!debug.contains(&format!("{:?}", kp.private_key())));
+    Ok(())
+}
+
 #[test]
 fn keccak256_correct_output() {
-    use blake2::Digest;
+    use sha3::Digest;
     use sha3::Keccak256;
     let input = "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq";
     let mut hasher = Keccak256::new();
@@ -308,7 +931,7 @@ fn key_pair_new_ecdsa_secp256k1() {
 
     assert!(key_entry.public_key.key_type == KeyType::EcdsaSecp256k1VerificationKey2019);
     assert_eq!(key_entry.public_key.controller, Vec::<String>::new());
-    assert_eq!(key_entry.private_key, test_sk);
+    assert_eq!(key_entry.private_key(), &test_sk[..]);
     assert_eq!(key_entry.public_key.public_key, expected_pk);
 }
 
@@ -324,8 +947,12 @@ fn key_pair_new_ecdsa_x25519() -> Result<(), Error> {
 
     assert!(key_entry.public_key.key_type == KeyType::X25519KeyAgreementKey2019);
     assert_eq!(key_entry.public_key.controller, Vec::<String>::new());
-    assert_eq!(key_entry.private_key, test_sk);
+    assert_eq!(key_entry.private_key(), &test_sk[..]);
     assert_eq!(key_entry.public_key.public_key, expected_pk);
+    assert!(matches!(
+        KeyPair::new(KeyType::X25519KeyAgreementKey2019, &test_sk[..31]),
+        Err(Error::WrongKeyLength)
+    ));
     Ok(())
 }
 
@@ -343,7 +970,7 @@ fn ecdh_test_1() -> Result<(), Error> {
 
     let test_kp = KeyPair::new(KeyType::X25519KeyAgreementKey2019, &sk)?;
 
-    assert_eq!(test_kp.ecdh_key_agreement(&pk)?, ak);
+    assert_eq!(*test_kp.ecdh_key_agreement(&pk)?, ak);
     Ok(())
 }
 
@@ -361,7 +988,7 @@ fn ecdh_test_2() -> Result<(), Error> {
 
     let test_kp = KeyPair::new(KeyType::X25519KeyAgreementKey2019, &sk)?;
 
-    assert_eq!(test_kp.ecdh_key_agreement(&pk)?, ak);
+    assert_eq!(*test_kp.ecdh_key_agreement(&pk)?, ak);
     Ok(())
 }
 
@@ -371,8 +998,8 @@ fn key_deser() -> Result<(), Error> {
     let kp: KeyPair = serde_json::from_str(content)?;
 
     assert_eq!(
-        kp.private_key,
-        hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").unwrap()
+        kp.private_key(),
+        &hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").unwrap()[..]
     );
     assert_eq!(
         kp.public_key.public_key,
@@ -383,3 +1010,500 @@ fn key_deser() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn schnorr_bip340_sign_vectors() -> Result<(), Error> {
+    // test vectors 0 and 1 from https://github.com/bitcoin/bips/blob/master/bip-0340/test-vectors.csv
+    let vectors = [
+        (
+            "0000000000000000000000000000000000000000000000000000000000000003",
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            "e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca821525f66a4a85ea8b71e482a74f382d2ce5ebeee8fdb2172f477df4900d310536c0",
+        ),
+        (
+            "b7e151628aed2a6abf7158809cf4f3c762e7160f38b4da56a784d9045190cfef",
+            "dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659",
+            "0000000000000000000000000000000000000000000000000000000000000001",
+            "243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89",
+            "6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a",
+        ),
+    ];
+    for (sk, pk, aux, msg, sig) in vectors.iter() {
+        let sk = hex::decode(sk).unwrap();
+        let aux = hex::decode(aux).unwrap();
+        let msg = hex::decode(msg).unwrap();
+        let kp = KeyPair::new(KeyType::SchnorrSecp256k1VerificationKey2019, &sk)?;
+        assert_eq!(kp.public_key.public_key, hex::decode(pk).unwrap());
+        assert_eq!(kp.private_key(), &sk[..]);
+        let signature = kp.sign_prehashed(&msg, &<[u8; 32]>::try_from(&aux[..]).unwrap())?;
+        assert_eq!(signature, hex::decode(sig).unwrap());
+        assert!(kp.public_key.verify_prehashed(&msg, &signature)?);
+    }
+    Ok(())
+}
+
+#[test]
+fn schnorr_random_pair() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::SchnorrSecp256k1VerificationKey2019)?;
+    assert_eq!(kp.public_key.public_key.len(), 32);
+    let signature = kp.sign(b"any message")?;
+    assert!(kp.public_key.verify(b"any message", &signature)?);
+    assert!(!kp.public_key.verify(b"other message", &signature)?);
+    // `sign` hashes the message with SHA-256
+    let hash = Sha256::digest(b"any message");
+    assert!(kp.public_key.verify_prehashed(&hash, &signature)?);
+    assert!(matches!(
+        kp.sign_prehashed(b"not hashed", &[0u8; 32]),
+        Err(Error::WrongMessageLength)
+    ));
+    Ok(())
+}
+
+#[test]
+fn bls_key_pair_new() -> Result<(), Error> {
+    for kt in [KeyType::Bls12381G1Key2020, KeyType::Bls12381G2Key2020].iter() {
+        let random = KeyPair::random_pair(*kt)?;
+        let kp = KeyPair::new(*kt, random.private_key())?;
+        assert_eq!(kp.public_key.public_key, random.public_key.public_key);
+        let signature = kp.sign(b"message")?;
+        assert!(random.public_key.verify(b"message", &signature)?);
+        assert!(!random.public_key.verify(b"other message", &signature)?);
+        assert!(matches!(
+            random.public_key.verify(b"message", &signature[1..]),
+            Err(Error::BlsCryptoError(_))
+        ));
+        assert!(matches!(
+            KeyPair::new(*kt, &[1u8; 31]),
+            Err(Error::BlsCryptoError(_))
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn bls_aggregation() -> Result<(), Error> {
+    use super::bls::{
+        aggregate_public_keys, aggregate_signatures, verify_aggregate, verify_aggregate_distinct,
+    };
+    for kt in [KeyType::Bls12381G1Key2020, KeyType::Bls12381G2Key2020].iter() {
+        let signers = [
+            KeyPair::random_pair(*kt)?,
+            KeyPair::random_pair(*kt)?,
+            KeyPair::random_pair(*kt)?,
+        ];
+        let public_keys: Vec<PublicKeyInfo> =
+            signers.iter().map(|kp| kp.get_public_key()).collect();
+        for kp in signers.iter() {
+            assert!(kp.public_key.verify_possession(&kp.prove_possession()?)?);
+        }
+        assert!(!public_keys[1].verify_possession(&signers[0].prove_possession()?)?);
+
+        // same message
+        let signatures = signers
+            .iter()
+            .map(|kp| kp.sign(b"attestation"))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let aggregate = aggregate_signatures(*kt, &signatures)?;
+        assert_eq!(aggregate.len(), signatures[0].len());
+        assert!(verify_aggregate(&public_keys, b"attestation", &aggregate)?);
+        assert!(!verify_aggregate(&public_keys, b"other", &aggregate)?);
+        assert!(!verify_aggregate(
+            &public_keys[..2],
+            b"attestation",
+            &aggregate
+        )?);
+        assert!(aggregate_public_keys(&public_keys)?.verify(b"attestation", &aggregate)?);
+
+        // distinct messages
+        let messages = [&b"first"[..], b"second", b"third"];
+        let signatures = signers
+            .iter()
+            .zip(messages.iter())
+            .map(|(kp, m)| kp.sign(m))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let aggregate = aggregate_signatures(*kt, &signatures)?;
+        assert!(verify_aggregate_distinct(
+            &public_keys,
+            &messages,
+            &aggregate
+        )?);
+        let swapped = [&b"second"[..], b"first", b"third"];
+        assert!(!verify_aggregate_distinct(
+            &public_keys,
+            &swapped,
+            &aggregate
+        )?);
+        let repeated = [&b"first"[..], b"first", b"third"];
+        assert!(!verify_aggregate_distinct(
+            &public_keys,
+            &repeated,
+            &aggregate
+        )?);
+        assert!(matches!(
+            verify_aggregate_distinct(&public_keys, &messages[..2], &aggregate),
+            Err(Error::BlsCryptoError(_))
+        ));
+    }
+    let g1 = KeyPair::random_pair(KeyType::Bls12381G1Key2020)?.get_public_key();
+    let g2 = KeyPair::random_pair(KeyType::Bls12381G2Key2020)?.get_public_key();
+    assert!(matches!(
+        verify_aggregate(&[g1, g2], b"attestation", &[0u8; 48]),
+        Err(Error::WrongKeyType)
+    ));
+    assert!(matches!(
+        aggregate_public_keys(&[]),
+        Err(Error::BlsCryptoError(_))
+    ));
+    assert!(matches!(
+        KeyPair::random_pair(KeyType::Ed25519VerificationKey2018)?.prove_possession(),
+        Err(Error::WrongKeyType)
+    ));
+    Ok(())
+}
+
+#[test]
+fn bbs_selective_disclosure() -> Result<(), Error> {
+    let issuer = KeyPair::random_pair(KeyType::Bls12381G2Key2020)?;
+    let claims = [
+        &b"name: Alice"[..],
+        b"birth date: 1990-01-01",
+        b"country: NZ",
+    ];
+    let signature = issuer.sign_bbs(&claims)?;
+    let pk = issuer.get_public_key();
+    assert!(pk.verify_bbs(&claims, &signature)?);
+    assert!(!pk.verify_bbs(&[&b"name: Mallory"[..], claims[1], claims[2]], &signature)?);
+
+    // holder discloses the name and the country only
+    let proof = pk.derive_bbs_proof(&claims, &signature, &[0, 2], b"nonce")?;
+    let revealed = [(0, claims[0]), (2, claims[2])];
+    assert!(pk.verify_bbs_proof(3, &revealed, &proof, b"nonce")?);
+    assert!(!pk.verify_bbs_proof(3, &revealed, &proof, b"other nonce")?);
+    assert!(!pk.verify_bbs_proof(
+        3,
+        &[(0, claims[0]), (2, &b"country: AU"[..])],
+        &proof,
+        b"nonce"
+    )?);
+    assert!(!pk.verify_bbs_proof(3, &[(0, claims[0]), (1, claims[1])], &proof, b"nonce")?);
+
+    let g1 = KeyPair::random_pair(KeyType::Bls12381G1Key2020)?;
+    assert!(matches!(g1.sign_bbs(&claims), Err(Error::WrongKeyType)));
+    Ok(())
+}
+
+#[test]
+fn ed25519_to_x25519_conversion() -> Result<(), Error> {
+    let ed25519 = KeyPair::random_pair(KeyType::Ed25519VerificationKey2020)?
+        .set_controller(vec!["did:key:z6Mk".into()]);
+    let x25519 = ed25519.to_x25519()?;
+    assert_eq!(
+        x25519.public_key.key_type,
+        KeyType::X25519KeyAgreementKey2020
+    );
+    assert_eq!(x25519.public_key.controller, ed25519.public_key.controller);
+    assert_eq!(
+        x25519.public_key.public_key,
+        ed25519.public_key.to_x25519()?.public_key
+    );
+    assert_eq!(
+        ed25519.public_key.to_multikey()?.to_x25519()?.public_key,
+        x25519.public_key.public_key
+    );
+
+    let peer = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    assert_eq!(
+        x25519.ecdh_key_agreement(&peer.public_key.public_key)?,
+        peer.ecdh_key_agreement(&ed25519.public_key.to_x25519()?.public_key)?
+    );
+    assert!(matches!(x25519.to_x25519(), Err(Error::WrongKeyType)));
+    assert!(matches!(
+        PublicKeyInfo::new(KeyType::Ed25519VerificationKey2018, &[1u8; 31]).to_x25519(),
+        Err(Error::InvalidPublicKey)
+    ));
+    Ok(())
+}
+
+#[test]
+fn key_pair_p256_rfc6979() -> Result<(), Error> {
+    // key and SHA-256 signature of "sample" from https://www.rfc-editor.org/rfc/rfc6979#appendix-A.2.5
+    let test_sk =
+        hex::decode("c9afa9d845ba75166b5c215767b1d6934e50c3db36e89b127b8a622b120f6721").unwrap();
+    let expected_pk =
+        hex::decode("0360fed4ba255a9d31c961eb74c6356d68c049b8923b61fa6ce669622e60f29fb6").unwrap();
+    let expected_sig = hex::decode("efd48b2aacb6a8fd1140dd9cd45e81d69d2c877b56aaf991c34d0ea84eaf3716f7cb1c942d657c41d436c7a1b6e29f65f3e900dbb9aff4064dc4ab2f843acda8").unwrap();
+
+    let key_entry = KeyPair::new(KeyType::JwsVerificationKey2020, &test_sk)?;
+
+    assert_eq!(key_entry.public_key.public_key, expected_pk);
+    assert_eq!(key_entry.private_key(), &test_sk[..]);
+    assert_eq!(key_entry.sign(b"sample")?, expected_sig);
+    assert!(key_entry.public_key.verify(b"sample", &expected_sig)?);
+    Ok(())
+}
+
+#[test]
+fn p256_random_pair() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::JwsVerificationKey2020)?;
+    assert_eq!(kp.public_key.public_key.len(), 33);
+    let signature = kp.sign(b"message")?;
+    assert!(kp.public_key.verify(b"message", &signature)?);
+    assert!(!kp.public_key.verify(b"other message", &signature)?);
+    Ok(())
+}
+
+#[test]
+fn ecdh_secp256k1() -> Result<(), Error> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    let alice = KeyPair::random_pair(KeyType::EcdsaSecp256k1VerificationKey2019)?;
+    let bob = KeyPair::random_pair(KeyType::EcdsaSecp256k1RecoveryMethod2020)?;
+    let shared = alice.ecdh_key_agreement(&bob.public_key.public_key)?;
+    assert_eq!(
+        shared,
+        bob.ecdh_key_agreement(&alice.public_key.public_key)?
+    );
+
+    // x-coordinate of bob's point multiplied by alice's scalar
+    let bob_point = k256::PublicKey::from_sec1_bytes(&bob.public_key.public_key).unwrap();
+    let alice_scalar = k256::SecretKey::from_slice(alice.private_key()).unwrap();
+    let point =
+        (bob_point.to_projective() * *alice_scalar.to_nonzero_scalar()).to_encoded_point(true);
+    assert_eq!(&shared[..], &point.as_bytes()[1..]);
+
+    // uncompressed peer keys are accepted as well
+    let uncompressed = bob_point.to_encoded_point(false);
+    assert_eq!(alice.ecdh_key_agreement(uncompressed.as_bytes())?, shared);
+    assert!(matches!(
+        alice.ecdh_key_agreement(&bob.public_key.public_key[..32]),
+        Err(Error::InvalidPublicKey)
+    ));
+    Ok(())
+}
+
+#[test]
+fn ecdh_x25519_peer_key_validation() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    for invalid in [&[9u8; 31][..], &[9u8; 33], &[], &[0u8; 32]].iter() {
+        assert!(matches!(
+            kp.ecdh_key_agreement(invalid),
+            Err(Error::InvalidPublicKey)
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn ecdh_p256() -> Result<(), Error> {
+    // test vector COUNT = 0 from NIST CAVS 14.1 ECC CDH Primitive, P-256
+    let sk =
+        hex::decode("7d7dc5f71eb29ddaf80d6214632eeae03d9058af1fb6d22ed80badb62bc1a534").unwrap();
+    let pk = hex::decode("04700c48f77f56584c5cc632ca65640db91b6bacce3a4df6b42ce7cc838833d287db71e509e3fd9b060ddb20ba5c51dcc5948d46fbf640dfe0441782cab85fa4ac").unwrap();
+    let ak =
+        hex::decode("46fc62106420ff012e54a434fbdd2d25ccc5852060561e68040dd7778997bd7b").unwrap();
+
+    let test_kp = KeyPair::new(KeyType::JwsVerificationKey2020, &sk)?;
+
+    assert_eq!(*test_kp.ecdh_key_agreement(&pk)?, ak);
+    assert!(matches!(
+        test_kp.ecdh_key_agreement(&pk[..32]),
+        Err(Error::InvalidPublicKey)
+    ));
+    Ok(())
+}
+
+#[test]
+fn rsa_sign_verify_decrypt() -> Result<(), Error> {
+    assert!(matches!(
+        KeyPair::random_rsa_pair(1024),
+        Err(Error::WrongKeyLength)
+    ));
+    let kp = KeyPair::random_pair(KeyType::RsaVerificationKey2018)?;
+    let restored = KeyPair::new(KeyType::RsaVerificationKey2018, kp.private_key())?;
+    assert_eq!(restored.public_key.public_key, kp.public_key.public_key);
+
+    let rs256 = kp.sign(b"message")?;
+    assert_eq!(rs256.len(), 256);
+    // PKCS#1 v1.5 signatures are deterministic
+    assert_eq!(restored.sign(b"message")?, rs256);
+    assert!(kp.public_key.verify(b"message", &rs256)?);
+    assert!(!kp.public_key.verify(b"other message", &rs256)?);
+
+    let ps256 = kp.sign_pss(b"message")?;
+    assert_ne!(ps256, rs256);
+    assert!(kp.public_key.verify_pss(b"message", &ps256)?);
+    assert!(!kp.public_key.verify_pss(b"other message", &ps256)?);
+    // each verification accepts its own padding only
+    assert!(!kp.public_key.verify(b"message", &ps256)?);
+    assert!(!kp.public_key.verify_pss(b"message", &rs256)?);
+
+    let cipher = kp.public_key.encrypt(b"secret", None)?;
+    assert_eq!(kp.decrypt(&cipher, None)?, b"secret");
+    assert!(KeyPair::random_pair(KeyType::Ed25519VerificationKey2018)?
+        .sign_pss(b"message")
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn key_pair_p384_rfc6979() -> Result<(), Error> {
+    // key and SHA-384 signature of "sample" from https://www.rfc-editor.org/rfc/rfc6979#appendix-A.2.6
+    let test_sk = hex::decode("6b9d3dad2e1b8c1c05b19875b6659f4de23c3b667bf297ba9aa47740787137d896d5724e4c70a825f872c9ea60d2edf5").unwrap();
+    let expected_pk = hex::decode("02ec3a4e415b4e19a4568618029f427fa5da9a8bc4ae92e02e06aae5286b300c64def8f0ea9055866064a254515480bc13").unwrap();
+    let expected_sig = hex::decode("94edbb92a5ecb8aad4736e56c691916b3f88140666ce9fa73d64c4ea95ad133c81a648152e44acf96e36dd1e80fabe4699ef4aeb15f178cea1fe40db2603138f130e740a19624526203b6351d0a3a94fa329c145786e679e7b82c71a38628ac8").unwrap();
+
+    let key_entry = KeyPair::new(KeyType::EcdsaSecp384r1VerificationKey2019, &test_sk)?;
+
+    assert_eq!(key_entry.public_key.public_key, expected_pk);
+    assert_eq!(key_entry.private_key(), &test_sk[..]);
+    assert_eq!(key_entry.sign(b"sample")?, expected_sig);
+    assert!(key_entry.public_key.verify(b"sample", &expected_sig)?);
+    assert!(!key_entry.public_key.verify(b"test", &expected_sig)?);
+    Ok(())
+}
+
+#[test]
+fn key_pair_p521() -> Result<(), Error> {
+    // key and SHA-512 signature of "sample" from https://www.rfc-editor.org/rfc/rfc6979#appendix-A.2.7
+    let test_sk = hex::decode("00fad06daa62ba3b25d2fb40133da757205de67f5bb0018fee8c86e1b68c7e75caa896eb32f1f47c70855836a6d16fcc1466f6d8fbec67db89ec0c08b0e996b83538").unwrap();
+    let expected_pk = hex::decode("0301894550d0785932e00eaa23b694f213f8c3121f86dc97a04e5a7167db4e5bcd371123d46e45db6b5d5370a7f20fb633155d38ffa16d2bd761dcac474b9a2f5023a4").unwrap();
+    let expected_sig = hex::decode("00c328fafcbd79dd77850370c46325d987cb525569fb63c5d3bc53950e6d4c5f174e25a1ee9017b5d450606add152b534931d7d4e8455cc91f9b15bf05ec36e377fa00617cce7cf5064806c467f678d3b4080d6f1cc50af26ca209417308281b68af282623eaa63e5b5c0723d8b8c37ff0777b1a20f8ccb1dccc43997f1ee0e44da4a67a").unwrap();
+
+    let key_entry = KeyPair::new(KeyType::EcdsaSecp521r1VerificationKey2019, &test_sk)?;
+
+    assert_eq!(key_entry.public_key.public_key, expected_pk);
+    assert_eq!(key_entry.private_key(), &test_sk[..]);
+    assert!(key_entry.public_key.verify(b"sample", &expected_sig)?);
+    // P-521 signatures use random nonce, so only check they verify
+    let signature = key_entry.sign(b"sample")?;
+    assert_eq!(signature.len(), 132);
+    assert!(key_entry.public_key.verify(b"sample", &signature)?);
+    assert!(!key_entry.public_key.verify(b"test", &expected_sig)?);
+    Ok(())
+}
+
+#[test]
+fn ecdh_p384() -> Result<(), Error> {
+    // test vector COUNT = 0 from NIST CAVS 14.1 ECC CDH Primitive, P-384
+    let sk = hex::decode("3cc3122a68f0d95027ad38c067916ba0eb8c38894d22e1b15618b6818a661774ad463b205da88cf699ab4d43c9cf98a1").unwrap();
+    let pk = hex::decode("04a7c76b970c3b5fe8b05d2838ae04ab47697b9eaf52e764592efda27fe7513272734466b400091adbf2d68c58e0c50066ac68f19f2e1cb879aed43a9969b91a0839c4c38a49749b661efedf243451915ed0905a32b060992b468c64766fc8437a").unwrap();
+    let ak = hex::decode("5f9d29dc5e31a163060356213669c8ce132e22f57c9a04f40ba7fcead493b457e5621e766c40a2e3d4d6a04b25e533f1").unwrap();
+
+    let test_kp = KeyPair::new(KeyType::EcdsaSecp384r1VerificationKey2019, &sk)?;
+
+    assert_eq!(*test_kp.ecdh_key_agreement(&pk)?, ak);
+    Ok(())
+}
+
+#[test]
+fn ecdh_p521() -> Result<(), Error> {
+    let alice = KeyPair::random_pair(KeyType::EcdsaSecp521r1VerificationKey2019)?;
+    let bob = KeyPair::random_pair(KeyType::EcdsaSecp521r1VerificationKey2019)?;
+    let shared = alice.ecdh_key_agreement(&bob.public_key.public_key)?;
+    assert_eq!(shared.len(), 66);
+    assert_eq!(
+        bob.ecdh_key_agreement(&alice.public_key.public_key)?,
+        shared
+    );
+    assert!(matches!(
+        alice.ecdh_key_agreement(&bob.public_key.public_key[1..]),
+        Err(Error::InvalidPublicKey)
+    ));
+    Ok(())
+}
+
+#[test]
+fn key_pair_2020_suites_round_trip() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::Ed25519VerificationKey2020)?;
+    let json = serde_json::to_string(&kp)?;
+    assert!(json.contains("publicKeyMultibase"));
+    let parsed: KeyPair = serde_json::from_str(&json)?;
+    assert_eq!(parsed.public_key.public_key, kp.public_key.public_key);
+    assert_eq!(parsed.private_key(), kp.private_key());
+    assert!(kp
+        .public_key
+        .verify(b"message", &parsed.sign(b"message")?)?);
+
+    let alice = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2020)?.to_multikey()?;
+    let bob = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let alice: KeyPair = serde_json::from_str(&serde_json::to_string(&alice)?)?;
+    assert_eq!(alice.public_key.key_type, KeyType::Multikey);
+    assert_eq!(
+        alice.ecdh_key_agreement(&bob.public_key.public_key)?,
+        bob.ecdh_key_agreement(&alice.public_key.resolve_multikey()?.public_key)?
+    );
+    let cipher = alice.public_key.encrypt(b"secret", None)?;
+    assert_eq!(alice.decrypt(&cipher, None)?, b"secret");
+    Ok(())
+}
+
+#[test]
+fn private_key_encodings_round_trip() -> Result<(), Error> {
+    let sk =
+        hex::decode("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a").unwrap();
+    let kp = KeyPair::new(KeyType::X25519KeyAgreementKey2019, &sk)?;
+    let formats = [
+        (PrivateKeyFormat::Hex, "privateKeyHex"),
+        (PrivateKeyFormat::Base64, "privateKeyBase64"),
+        (PrivateKeyFormat::Base58, "privateKeyBase58"),
+        (PrivateKeyFormat::Multibase, "privateKeyMultibase"),
+    ];
+    for (format, field) in formats.iter() {
+        let json = serde_json::to_value(kp.clone().encoding(format.clone()))?;
+        assert!(json.get(field).is_some());
+        assert_eq!(json.as_object().unwrap().len(), 4);
+        let parsed: KeyPair = serde_json::from_value(json.clone())?;
+        assert_eq!(parsed.private_key(), kp.private_key());
+        assert_eq!(parsed.get_encoding(), Some(format));
+        // encoding is kept
+        assert_eq!(serde_json::to_value(&parsed)?, json);
+    }
+
+    // 2020 suites default to multibase
+    let json = serde_json::to_value(&KeyPair::new(KeyType::X25519KeyAgreementKey2020, &sk)?)?;
+    assert!(json["privateKeyMultibase"]
+        .as_str()
+        .unwrap()
+        .starts_with('z'));
+    let multikey = serde_json::to_value(&kp.to_multikey()?)?;
+    let parsed: KeyPair = serde_json::from_value(multikey)?;
+    assert_eq!(parsed.private_key(), kp.private_key());
+    Ok(())
+}
+
+#[test]
+fn private_keys_kept_elsewhere() -> Result<(), Error> {
+    let kp = KeyPair::random_pair(KeyType::Ed25519VerificationKey2018)?;
+    let kms = KeyPair::from_encoding(
+        kp.get_public_key(),
+        &PrivateKeyEncoding::PrivateKeyWebKms("kms-key-1".into()),
+    )?;
+    assert!(kms.private_key().is_empty());
+    assert!(matches!(
+        kms.sign(b"data"),
+        Err(Error::PrivateKeyUnavailable)
+    ));
+    assert!(matches!(
+        kms.encode(&PrivateKeyFormat::Hex),
+        Err(Error::PrivateKeyUnavailable)
+    ));
+    let json = serde_json::to_value(&kms)?;
+    assert_eq!(json["privateKeyWebKms"], "kms-key-1");
+
+    // seed reference replaces private key material
+    let seeded = kp.encoding(PrivateKeyFormat::FromSeed {
+        path: "m/0'".into(),
+        seed_ref: "seed".into(),
+    });
+    let json = serde_json::to_value(&seeded)?;
+    assert_eq!(
+        json["privateKeyFromSeed"],
+        serde_json::json!({"path": "m/0'", "seedRef": "seed"})
+    );
+    assert!(json.get("privateKeyHex").is_none());
+    let parsed: KeyPair = serde_json::from_value(json)?;
+    assert!(parsed.private_key().is_empty());
+    assert_eq!(parsed.get_encoding(), seeded.get_encoding());
+    Ok(())
+}
@@ -1,12 +1,16 @@
+mod bbs_plus;
+pub mod bls;
+pub mod derivation;
 pub mod encryption;
 pub mod entropy;
 pub mod key_pair;
+mod multicodec;
 pub mod public_key_info;
 
 use serde::{ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
 
 use public_key_info::PublicKeyInfo;
-use std::collections::hash_map::*;
+use std::{collections::hash_map::*, fmt};
 use uuid::Uuid;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -29,6 +33,8 @@ impl ContentEntity {
     }
 }
 
+/// Single item stored in the wallet.
+/// `Debug` output of secret bearing variants is redacted.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum Content {
@@ -37,10 +43,37 @@ pub enum Content {
     PublicKey(public_key_info::PublicKeyInfo),
 }
 
+/// Selects which secret material gets exported
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ExportMode {
+    /// Public keys only, like `ContentEntity::clean`. Entropy is left out.
+    PublicOnly,
+    /// Everything, including private keys and entropy
+    Full,
+}
+
 impl Content {
     pub fn to_entity(&self, id: &str) -> ContentEntity {
         content_to_entity(self, id)
     }
+
+    /// Returns content as it should be exported in the `mode` provided,
+    /// `None` if it should be left out
+    ///
+    /// # Parameters
+    ///
+    /// * mode - `ExportMode` to apply
+    ///
+    pub fn export(&self, mode: ExportMode) -> Option<Content> {
+        match (mode, self) {
+            (ExportMode::Full, _) => Some(self.clone()),
+            (ExportMode::PublicOnly, Content::KeyPair(kp)) => {
+                Some(Content::PublicKey(kp.get_public_key()))
+            }
+            (ExportMode::PublicOnly, Content::PublicKey(_)) => Some(self.clone()),
+            (ExportMode::PublicOnly, Content::Entropy(_)) => None,
+        }
+    }
 }
 
 fn content_to_entity(content: &Content, id: &str) -> ContentEntity {
@@ -116,6 +149,16 @@ impl Contents {
             .collect()
     }
 
+    /// Copy of the contents as they should be exported in the `mode` provided
+    pub fn export(&self, mode: ExportMode) -> Self {
+        Self(
+            self.0
+                .iter()
+                .filter_map(|(id, content)| Some((id.clone(), content.export(mode)?)))
+                .collect(),
+        )
+    }
+
     /// Replace the id of associated with an old content.
     pub fn replace_key(&mut self, old_id: &str, new_id: &str) -> Option<Content> {
         let value = self.0.remove(old_id)?;
@@ -123,6 +166,12 @@ impl Contents {
     }
 }
 
+impl fmt::Debug for Contents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter()).finish()
+    }
+}
+
 impl Serialize for Contents {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -0,0 +1,169 @@
+use super::public_key_info::KeyType;
+use crate::Error;
+use zeroize::Zeroizing;
+
+/// Varint encoded multicodec prefixes of public keys.
+/// [Table](https://github.com/multiformats/multicodec/blob/master/table.csv)
+const CODECS: &[(KeyType, [u8; 2])] = &[
+    (KeyType::Ed25519VerificationKey2020, [0xed, 0x01]),
+    (KeyType::X25519KeyAgreementKey2020, [0xec, 0x01]),
+    (KeyType::EcdsaSecp256k1VerificationKey2019, [0xe7, 0x01]),
+    (KeyType::Bls12381G1Key2020, [0xea, 0x01]),
+    (KeyType::Bls12381G2Key2020, [0xeb, 0x01]),
+    (KeyType::JwsVerificationKey2020, [0x80, 0x24]),
+    (KeyType::EcdsaSecp384r1VerificationKey2019, [0x81, 0x24]),
+    (KeyType::EcdsaSecp521r1VerificationKey2019, [0x82, 0x24]),
+    (KeyType::RsaVerificationKey2018, [0x85, 0x24]),
+];
+
+/// Varint encoded multicodec prefixes of private keys.
+const PRIVATE_CODECS: &[(KeyType, [u8; 2])] = &[
+    (KeyType::Ed25519VerificationKey2020, [0x80, 0x26]),
+    (KeyType::EcdsaSecp256k1VerificationKey2019, [0x81, 0x26]),
+    (KeyType::X25519KeyAgreementKey2020, [0x82, 0x26]),
+    (KeyType::RsaVerificationKey2018, [0x85, 0x26]),
+    (KeyType::JwsVerificationKey2020, [0x86, 0x26]),
+    (KeyType::EcdsaSecp384r1VerificationKey2019, [0x87, 0x26]),
+    (KeyType::EcdsaSecp521r1VerificationKey2019, [0x88, 0x26]),
+    (KeyType::Bls12381G1Key2020, [0x89, 0x26]),
+    (KeyType::Bls12381G2Key2020, [0x8a, 0x26]),
+];
+
+/// Multicodec prefix of public keys of `key_type`, if there is one
+pub(crate) fn prefix(key_type: KeyType) -> Option<&'static [u8]> {
+    lookup(CODECS, key_type)
+}
+
+/// Multicodec prefix of private keys of `key_type`, if there is one
+pub(crate) fn private_prefix(key_type: KeyType) -> Option<&'static [u8]> {
+    lookup(PRIVATE_CODECS, key_type)
+}
+
+// finds prefix of `key_type` in the `codecs` table, older suites share codecs with newer ones
+fn lookup(codecs: &'static [(KeyType, [u8; 2])], key_type: KeyType) -> Option<&'static [u8]> {
+    let key_type = match key_type {
+        KeyType::Ed25519VerificationKey2018 => KeyType::Ed25519VerificationKey2020,
+        KeyType::X25519KeyAgreementKey2019 => KeyType::X25519KeyAgreementKey2020,
+        KeyType::EcdsaSecp256k1RecoveryMethod2020 => KeyType::EcdsaSecp256k1VerificationKey2019,
+        other => other,
+    };
+    codecs
+        .iter()
+        .find(|(kt, _)| *kt == key_type)
+        .map(|(_, prefix)| &prefix[..])
+}
+
+/// Splits multicodec prefixed public key into its `KeyType` and raw key
+pub(crate) fn split(bytes: &[u8]) -> Result<(KeyType, &[u8]), Error> {
+    CODECS
+        .iter()
+        .find(|(_, prefix)| bytes.starts_with(prefix))
+        .map(|(kt, prefix)| (*kt, &bytes[prefix.len()..]))
+        .ok_or(Error::InvalidPublicKey)
+}
+
+/// Encodes bytes as base58btc multibase string
+pub(crate) fn to_multibase(bytes: &[u8]) -> String {
+    format!("z{}", bs58::encode(bytes).into_string())
+}
+
+/// Decodes base58btc multibase string
+pub(crate) fn from_multibase(encoded: &str) -> Result<Vec<u8>, Error> {
+    match encoded.strip_prefix('z') {
+        Some(base58) => bs58::decode(base58)
+            .into_vec()
+            .map_err(|_| Error::InvalidPublicKey),
+        None => Err(Error::InvalidPublicKey),
+    }
+}
+
+/// Encodes raw public key of `key_type` as `publicKeyMultibase` value.
+/// `Multikey`s are expected to be prefixed already.
+pub(crate) fn encode_public_key(key_type: KeyType, public_key: &[u8]) -> Result<String, Error> {
+    if key_type == KeyType::Multikey {
+        return Ok(to_multibase(public_key));
+    }
+    let prefix = prefix(key_type).ok_or(Error::UnsupportedKeyType)?;
+    Ok(to_multibase(&[prefix, public_key].concat()))
+}
+
+/// Decodes `publicKeyMultibase` value into raw public key of `key_type`.
+/// `Multikey`s keep their prefix, as it is the only place their type is kept in.
+pub(crate) fn decode_public_key(key_type: KeyType, encoded: &str) -> Result<Vec<u8>, Error> {
+    let bytes = from_multibase(encoded)?;
+    let (decoded_type, raw) = split(&bytes)?;
+    if key_type == KeyType::Multikey {
+        return Ok(bytes);
+    }
+    if prefix(key_type) != prefix(decoded_type) {
+        return Err(Error::InvalidPublicKey);
+    }
+    Ok(raw.to_vec())
+}
+
+/// Encodes raw private key of `key_type` as `privateKeyMultibase` value.
+/// `key_type` must be a concrete one, not `Multikey`.
+pub(crate) fn encode_private_key(key_type: KeyType, private_key: &[u8]) -> Result<String, Error> {
+    let prefix = private_prefix(key_type).ok_or(Error::UnsupportedKeyType)?;
+    Ok(to_multibase(&Zeroizing::new(
+        [prefix, private_key].concat(),
+    )))
+}
+
+/// Decodes `privateKeyMultibase` value into raw private key of `key_type`.
+pub(crate) fn decode_private_key(
+    key_type: KeyType,
+    encoded: &str,
+) -> Result<Zeroizing<Vec<u8>>, Error> {
+    let prefix = private_prefix(key_type).ok_or(Error::UnsupportedKeyType)?;
+    let bytes = Zeroizing::new(from_multibase(encoded).map_err(|_| Error::InvalidPrivateKey)?);
+    match bytes.strip_prefix(prefix) {
+        Some(raw) => Ok(Zeroizing::new(raw.to_vec())),
+        None => Err(Error::InvalidPrivateKey),
+    }
+}
+
+#[test]
+fn multibase_prefixes() -> Result<(), Error> {
+    // well known did:key prefixes
+    let cases = [
+        (KeyType::Ed25519VerificationKey2020, 32, "z6Mk"),
+        (KeyType::X25519KeyAgreementKey2020, 32, "z6LS"),
+        (KeyType::EcdsaSecp256k1VerificationKey2019, 33, "zQ3s"),
+        (KeyType::JwsVerificationKey2020, 33, "zDn"),
+    ];
+    for (kt, len, expected) in cases.iter() {
+        let key = vec![2u8; *len];
+        let encoded = encode_public_key(*kt, &key)?;
+        assert!(encoded.starts_with(expected));
+        assert_eq!(decode_public_key(*kt, &encoded)?, key);
+        assert_eq!(split(&from_multibase(&encoded)?)?, (*kt, &key[..]));
+    }
+    let ed25519 = encode_public_key(KeyType::Ed25519VerificationKey2018, &[1u8; 32])?;
+    assert!(matches!(
+        decode_public_key(KeyType::X25519KeyAgreementKey2020, &ed25519),
+        Err(Error::InvalidPublicKey)
+    ));
+    assert!(from_multibase("uAAAA").is_err());
+    Ok(())
+}
+
+#[test]
+fn private_multibase_prefixes() -> Result<(), Error> {
+    let sk = [3u8; 32];
+    let encoded = encode_private_key(KeyType::Ed25519VerificationKey2018, &sk)?;
+    assert!(encoded.starts_with("z3u2"));
+    assert_eq!(
+        &decode_private_key(KeyType::Ed25519VerificationKey2020, &encoded)?[..],
+        &sk[..]
+    );
+    assert!(matches!(
+        decode_private_key(KeyType::X25519KeyAgreementKey2019, &encoded),
+        Err(Error::InvalidPrivateKey)
+    ));
+    assert!(matches!(
+        encode_private_key(KeyType::SchnorrSecp256k1VerificationKey2019, &sk),
+        Err(Error::UnsupportedKeyType)
+    ));
+    Ok(())
+}
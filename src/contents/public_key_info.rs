@@ -1,30 +1,79 @@
-use super::encryption::{KEYSIZE, seal_box};
+use super::{
+    bbs_plus,
+    bls,
+    encryption::{KEYSIZE, seal_box},
+    multicodec,
+};
 use core::str::FromStr;
-use std::convert::TryInto;
+use std::convert::{TryFrom, TryInto};
 use crypto_box::PublicKey;
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use serde::{Deserialize, Serialize};
 use k256::ecdsa::{
     self,
     SigningKey,
     Signature,
     VerifyingKey,
-    signature::Signer,
-    recoverable
+    RecoveryId,
 };
+use sha3::{Digest, Keccak256};
 use crate::Error;
 
 /// Holds public information on key, controller and type of the key.
+/// Public key is serialized in `encoding` format, which defaults to
+/// `publicKeyMultibase` for `Ed25519VerificationKey2020`,
+/// `X25519KeyAgreementKey2020` and `Multikey` types and to `publicKeyHex` otherwise.
+/// Deserializes from any `PublicKeyEncoding` but `EthereumAddress`.
 ///
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Deserialize, Clone, Debug)]
+#[serde(try_from = "PublicKeyInfoRepr")]
 pub struct PublicKeyInfo {
     /// key controller information.
     pub controller: Vec<String>,
     /// variant of `KeyType` representing type of the key.
-    #[serde(rename = "type")]
     pub key_type: KeyType,
     /// vector of bytes of public key.
-    #[serde(rename = "publicKeyHex", with = "hex")]
+    /// `Multikey` keys keep their multicodec prefix.
     pub public_key: Vec<u8>,
+    /// format public key is serialized in, `None` for the default one.
+    /// Set with `encoding`, read with `get_encoding`.
+    pub(crate) encoding: Option<PublicKeyFormat>,
+}
+
+// JSON shape of `PublicKeyInfo`
+#[derive(Serialize, Deserialize)]
+struct PublicKeyInfoRepr {
+    controller: Vec<String>,
+    #[serde(rename = "type")]
+    key_type: KeyType,
+    #[serde(flatten)]
+    public_key: PublicKeyEncoding,
+}
+
+impl TryFrom<PublicKeyInfoRepr> for PublicKeyInfo {
+    type Error = Error;
+
+    fn try_from(repr: PublicKeyInfoRepr) -> Result<Self, Self::Error> {
+        Ok(PublicKeyInfo::from_encoding(repr.key_type, &repr.public_key)?
+            .controller(repr.controller))
+    }
+}
+
+impl Serialize for PublicKeyInfo {
+    fn serialize<S: serde::Serializer>(&self, ser: S) -> Result<S::Ok, S::Error> {
+        let format = self.encoding.unwrap_or(match self.key_type {
+            KeyType::Ed25519VerificationKey2020
+            | KeyType::X25519KeyAgreementKey2020
+            | KeyType::Multikey => PublicKeyFormat::Multibase,
+            _ => PublicKeyFormat::Hex,
+        });
+        PublicKeyInfoRepr {
+            controller: self.controller.clone(),
+            key_type: self.key_type,
+            public_key: self.encode(format).map_err(serde::ser::Error::custom)?,
+        }
+        .serialize(ser)
+    }
 }
 
 impl PublicKeyInfo {
@@ -57,9 +106,81 @@ impl PublicKeyInfo {
             controller: vec![],
             key_type: kt,
             public_key: pk.to_vec(),
+            encoding: None,
         }
     }
 
+    /// Decodes public key of `KeyType` provided from any `PublicKeyEncoding`
+    /// but `EthereumAddress`, which can not be decoded back into the key.
+    /// Encoding is kept, so key is serialized the way it was received.
+    ///
+    /// # Parameters
+    ///
+    /// * kt - KeyType of encoded key;
+    /// * encoded - public key in one of supported encodings;
+    ///
+    pub fn from_encoding(kt: KeyType, encoded: &PublicKeyEncoding) -> Result<Self, Error> {
+        let pk = match encoded {
+            PublicKeyEncoding::PublicKeyHex(pk) => {
+                hex::decode(pk).map_err(|_| Error::InvalidPublicKey)?
+            }
+            PublicKeyEncoding::PublicKeyBase64(pk) => {
+                base64::decode_config(pk, base64::URL_SAFE).map_err(|_| Error::InvalidPublicKey)?
+            }
+            PublicKeyEncoding::PublicKeyBase58(pk) => bs58::decode(pk)
+                .into_vec()
+                .map_err(|_| Error::InvalidPublicKey)?,
+            PublicKeyEncoding::PublicKeyMultibase(pk) => multicodec::decode_public_key(kt, pk)?,
+            PublicKeyEncoding::EthereumAddress(_) => return Err(Error::OutputOnlyEncoding),
+        };
+        Ok(Self::new(kt, &pk).encoding(encoded.format()))
+    }
+
+    /// Encodes public key into `format` provided.
+    /// `Multibase` is prefixed with multicodec of the key type,
+    /// `EthereumAddress` is only available for secp256k1 keys.
+    ///
+    /// # Parameters
+    ///
+    /// * format - desired `PublicKeyFormat`
+    ///
+    pub fn encode(&self, format: PublicKeyFormat) -> Result<PublicKeyEncoding, Error> {
+        Ok(match format {
+            PublicKeyFormat::Hex => PublicKeyEncoding::PublicKeyHex(hex::encode(&self.public_key)),
+            PublicKeyFormat::Base64 => PublicKeyEncoding::PublicKeyBase64(base64::encode_config(
+                &self.public_key,
+                base64::URL_SAFE,
+            )),
+            PublicKeyFormat::Base58 => {
+                PublicKeyEncoding::PublicKeyBase58(bs58::encode(&self.public_key).into_string())
+            }
+            PublicKeyFormat::Multibase => PublicKeyEncoding::PublicKeyMultibase(
+                multicodec::encode_public_key(self.key_type, &self.public_key)?,
+            ),
+            PublicKeyFormat::EthereumAddress => {
+                PublicKeyEncoding::EthereumAddress(ethereum_address(self)?)
+            }
+        })
+    }
+
+    /// Sets format public key is serialized in and returns updated struct.
+    ///
+    /// # Parameters
+    ///
+    /// * format - `PublicKeyFormat` to be used for serialization.
+    ///
+    pub fn encoding(self, format: PublicKeyFormat) -> Self {
+        Self {
+            encoding: Some(format),
+            ..self
+        }
+    }
+
+    /// Returns format public key is serialized in, `None` for the default one.
+    pub fn get_encoding(&self) -> Option<PublicKeyFormat> {
+        self.encoding
+    }
+
     /// Sets controller property to provided value and returns updated struct.
     ///
     /// # Parameters
@@ -87,6 +208,58 @@ impl PublicKeyInfo {
         }
     }
 
+    /// Converts the key into `Multikey`, prefixing it with multicodec of its type.
+    /// `Multikey`s are returned as is.
+    pub fn to_multikey(&self) -> Result<Self, Error> {
+        if self.key_type == KeyType::Multikey {
+            return Ok(self.clone());
+        }
+        let prefix = multicodec::prefix(self.key_type).ok_or(Error::UnsupportedKeyType)?;
+        Ok(Self {
+            key_type: KeyType::Multikey,
+            public_key: [prefix, &self.public_key[..]].concat(),
+            encoding: None,
+            ..self.clone()
+        })
+    }
+
+    /// Resolves `Multikey` into key of concrete `KeyType` it is prefixed with.
+    /// Keys of other types are returned as is.
+    pub fn resolve_multikey(&self) -> Result<Self, Error> {
+        if self.key_type != KeyType::Multikey {
+            return Ok(self.clone());
+        }
+        let (key_type, public_key) = multicodec::split(&self.public_key)?;
+        Ok(Self {
+            key_type,
+            public_key: public_key.to_vec(),
+            encoding: None,
+            ..self.clone()
+        })
+    }
+
+    /// Converts Ed25519 public key into birationally equivalent X25519 one,
+    /// matching the key of `KeyPair::to_x25519`.
+    /// Edwards point is mapped to its Montgomery u-coordinate.
+    pub fn to_x25519(&self) -> Result<Self, Error> {
+        let resolved = self.resolve_multikey()?;
+        let key_type = match resolved.key_type {
+            KeyType::Ed25519VerificationKey2018 => KeyType::X25519KeyAgreementKey2019,
+            KeyType::Ed25519VerificationKey2020 => KeyType::X25519KeyAgreementKey2020,
+            _ => return Err(Error::WrongKeyType),
+        };
+        let point = <[u8; 32]>::try_from(&resolved.public_key[..])
+            .ok()
+            .and_then(|bytes| CompressedEdwardsY(bytes).decompress())
+            .ok_or(Error::InvalidPublicKey)?;
+        Ok(Self {
+            key_type,
+            public_key: point.to_montgomery().to_bytes().to_vec(),
+            encoding: None,
+            ..resolved
+        })
+    }
+
     // TODO: should this cover all the key types?
     /// Encrypts message using own keys.
     ///
@@ -116,13 +289,19 @@ impl PublicKeyInfo {
     /// # }
     pub fn encrypt(&self, data: &[u8], _aad: Option<&[u8]>) -> Result<Vec<u8>, Error> {
         match self.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.encrypt(data, _aad),
             // default use xChaCha20Poly1905
-            KeyType::X25519KeyAgreementKey2019 => {
-                let pk: [u8; KEYSIZE] = self.public_key[..KEYSIZE]
+            KeyType::X25519KeyAgreementKey2019 | KeyType::X25519KeyAgreementKey2020 => {
+                let pk: [u8; KEYSIZE] = self.public_key[..]
                     .try_into()
-                    .map_err(|_| Error::BoxToSmall)?;
+                    .map_err(|_| Error::InvalidPublicKey)?;
                 seal_box(data, &PublicKey::from(pk))
             }
+            // RSA-OAEP with SHA-256
+            KeyType::RsaVerificationKey2018 => {
+                let pk = rsa_public_key(&self.public_key)?;
+                Ok(pk.encrypt(&mut rand::rngs::OsRng, rsa::Oaep::new::<sha2::Sha256>(), data)?)
+            }
             _ => Err(Error::WrongKeyType),
         }
     }
@@ -187,58 +366,236 @@ impl PublicKeyInfo {
     /// ```
     pub fn verify(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
         match self.key_type {
-            KeyType::Ed25519VerificationKey2018 => {
-                use ed25519_dalek::{PublicKey, Verifier, Signature};
-                let pk = PublicKey::from_bytes(&self.public_key)
+            KeyType::Multikey => self.resolve_multikey()?.verify(data, signature),
+            KeyType::Ed25519VerificationKey2018 | KeyType::Ed25519VerificationKey2020 => {
+                use ed25519_dalek::{VerifyingKey, Verifier, Signature};
+                let public_key = <&[u8; 32]>::try_from(&self.public_key[..])
+                    .map_err(|_| Error::InvalidPublicKey)?;
+                let pk = VerifyingKey::from_bytes(public_key)
                     .map_err(|e| Error::Other(Box::new(e)))?;
                 if signature.len() != 64 {
                     return Err(Error::WrongKeyLength);
                 }
-                let owned_signature = signature.to_owned();
-                let array_signature = array_ref!(owned_signature, 0, 64).to_owned();
-                let signature = Signature::from(array_signature);
+                let signature = Signature::from_slice(signature).map_err(Error::EdCryptoError)?;
 
                 Ok(pk.verify(data, &signature).is_ok())
             },
             KeyType::EcdsaSecp256k1VerificationKey2019 => {
                 let vk = VerifyingKey::from_sec1_bytes(&self.public_key)?;
-                let s1: [u8; 32] = array_ref!(signature, 0, 32).to_owned();
-                let s2: [u8; 32] = array_ref!(signature, 32, 32).to_owned();
-                let sign = Signature::from_scalars(s1, s2)?;
+                let sign = Signature::from_slice(signature)?;
                 use k256::ecdsa::signature::Verifier;
                 Ok(vk.verify(data, &sign).is_ok())
             },
             KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
-                let s1: [u8; 32] = array_ref!(signature, 0, 32).to_owned();
-                let s2: [u8; 32] = array_ref!(signature, 32, 32).to_owned();
-                let rs = ecdsa::Signature::from_scalars(s1, s2)
-                    .map_err( Error::EdCryptoError)?;
-                let recovered_signature = recoverable::Signature::from_trial_recovery(
+                // `r || s`, optionally followed by the recovery id byte
+                if signature.len() != 64 && signature.len() != 65 {
+                    return Err(Error::WrongKeyLength);
+                }
+                let rs = ecdsa::Signature::from_slice(&signature[..64])
+                    .map_err( Error::EcdsaCryptoError)?;
+                // Ethereum style, message is hashed with Keccak256
+                let digest = Keccak256::digest(data);
+                let recovery_id = RecoveryId::trial_recovery_from_prehash(
                     &ecdsa::VerifyingKey::from_sec1_bytes(&self.public_key)?,
-                    data,
+                    &digest,
                     &rs
                 ).map_err( Error::EcdsaCryptoError)?;
 
-                let recovered_key = recovered_signature.recover_verify_key(data)
+                let recovered_key = VerifyingKey::recover_from_prehash(&digest, &rs, recovery_id)
                     .map_err( Error::EcdsaCryptoError)?;
 
                 let our_key = ecdsa::VerifyingKey::from_sec1_bytes(&self.public_key).map_err(Error::EcdsaCryptoError)?;
 
                 Ok(our_key == recovered_key)
             },
+            KeyType::JwsVerificationKey2020 => {
+                use p256::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+                let vk = VerifyingKey::from_sec1_bytes(&self.public_key)?;
+                let signature = Signature::try_from(signature)?;
+                Ok(vk.verify(data, &signature).is_ok())
+            },
+            KeyType::EcdsaSecp384r1VerificationKey2019 => {
+                use p384::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+                let vk = VerifyingKey::from_sec1_bytes(&self.public_key)?;
+                let signature = Signature::try_from(signature)?;
+                Ok(vk.verify(data, &signature).is_ok())
+            },
+            KeyType::EcdsaSecp521r1VerificationKey2019 => {
+                use p521::ecdsa::{Signature, VerifyingKey, signature::Verifier};
+                let vk = VerifyingKey::from_sec1_bytes(&self.public_key)
+                    .map_err(Error::P521CryptoError)?;
+                let signature = Signature::from_slice(signature).map_err(Error::P521CryptoError)?;
+                Ok(vk.verify(data, &signature).is_ok())
+            },
+            // RS256 signatures only, PS256 ones are checked by `verify_pss`
+            KeyType::RsaVerificationKey2018 => {
+                use rsa::{pkcs1v15::{Signature, VerifyingKey}, signature::Verifier};
+                use sha2::Sha256;
+                let vk = VerifyingKey::<Sha256>::new(rsa_public_key(&self.public_key)?);
+                Ok(Signature::try_from(signature)
+                    .and_then(|signature| vk.verify(data, &signature))
+                    .is_ok())
+            },
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                use sha2::Sha256;
+                self.verify_prehashed(&Sha256::digest(data), signature)
+            },
             KeyType::Bls12381G1Key2020 => {
-                use signature_bls::{SignatureVt, PublicKeyVt};
-                let pk = PublicKeyVt::from_bytes(array_ref!(&self.public_key, 0, 48)).unwrap();
-                Ok(SignatureVt::from_bytes(array_ref!(signature, 0, 96)).unwrap().verify(pk, signature).unwrap_u8() == 1u8)
+                let pk = bls::g1_public_key(&self.public_key)?;
+                Ok(bls::g1_key_signature(signature)?.verify(pk, data).unwrap_u8() == 1u8)
+            },
+            KeyType::Bls12381G2Key2020 => {
+                let pk = bls::g2_public_key(&self.public_key)?;
+                Ok(bls::g2_key_signature(signature)?.verify(pk, data).unwrap_u8() == 1u8)
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Verifies RSASSA-PSS signature using SHA-256 (PS256) made by `KeyPair::sign_pss`.
+    /// Supported by `RsaVerificationKey2018` keys only.
+    ///
+    /// # Parameters
+    ///
+    /// * data - original message
+    /// * signature - signature to be verified
+    ///
+    pub fn verify_pss(&self, data: &[u8], signature: &[u8]) -> Result<bool, Error> {
+        use rsa::{pss::{Signature, VerifyingKey}, signature::Verifier};
+        use sha2::Sha256;
+        match self.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.verify_pss(data, signature),
+            KeyType::RsaVerificationKey2018 => {
+                let vk = VerifyingKey::<Sha256>::new(rsa_public_key(&self.public_key)?);
+                Ok(Signature::try_from(signature)
+                    .and_then(|signature| vk.verify(data, &signature))
+                    .is_ok())
             },
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Verifies BIP340 Schnorr signature of 32 byte hash made by `KeyPair::sign_prehashed`.
+    /// Supported by `SchnorrSecp256k1VerificationKey2019` keys only.
+    ///
+    /// # Parameters
+    ///
+    /// * hash - 32 byte hash of the message
+    /// * signature - signature to be verified
+    ///
+    pub fn verify_prehashed(&self, hash: &[u8], signature: &[u8]) -> Result<bool, Error> {
+        use k256::{ecdsa::signature::hazmat::PrehashVerifier, schnorr};
+        match self.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.verify_prehashed(hash, signature),
+            KeyType::SchnorrSecp256k1VerificationKey2019 => {
+                if hash.len() != 32 {
+                    return Err(Error::WrongMessageLength);
+                }
+                let vk = schnorr::VerifyingKey::from_bytes(&self.public_key)?;
+                let signature = schnorr::Signature::try_from(signature)?;
+                Ok(vk.verify_prehash(hash, &signature).is_ok())
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Verifies BBS+ signature made by `KeyPair::sign_bbs`.
+    /// Supported by `Bls12381G2Key2020` keys only.
+    ///
+    /// # Parameters
+    ///
+    /// * messages - all the signed messages, in the signing order
+    /// * signature - BBS+ signature to be verified
+    ///
+    pub fn verify_bbs<M: AsRef<[u8]>>(
+        &self,
+        messages: &[M],
+        signature: &[u8],
+    ) -> Result<bool, Error> {
+        match self.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.verify_bbs(messages, signature),
+            KeyType::Bls12381G2Key2020 => bbs_plus::verify(&self.public_key, messages, signature),
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Derives zero-knowledge proof of BBS+ signature made with this key,
+    /// which discloses only the messages at `revealed` indexes.
+    /// Used by holder, so no private key is needed.
+    ///
+    /// # Parameters
+    ///
+    /// * messages - all the signed messages, in the signing order
+    /// * signature - BBS+ signature of `messages`
+    /// * revealed - indexes of messages to disclose
+    /// * nonce - verifier provided nonce the proof is bound to
+    ///
+    pub fn derive_bbs_proof<M: AsRef<[u8]>>(
+        &self,
+        messages: &[M],
+        signature: &[u8],
+        revealed: &[usize],
+        nonce: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        match self.key_type {
+            KeyType::Multikey => self
+                .resolve_multikey()?
+                .derive_bbs_proof(messages, signature, revealed, nonce),
             KeyType::Bls12381G2Key2020 => {
-                use signature_bls::{Signature, PublicKey};
-                let pk = PublicKey::from_bytes(array_ref!(&self.public_key, 0, 96)).unwrap();
-                Ok(Signature::from_bytes(array_ref!(signature, 0, 48)).unwrap().verify(pk, signature).unwrap_u8() == 1u8)
+                bbs_plus::derive_proof(&self.public_key, messages, signature, revealed, nonce)
             }
             _ => Err(Error::WrongKeyType),
         }
     }
+
+    /// Verifies selective disclosure proof made by `derive_bbs_proof`.
+    ///
+    /// # Parameters
+    ///
+    /// * message_count - number of originally signed messages
+    /// * revealed - disclosed messages along with their indexes
+    /// * proof - proof to be verified
+    /// * nonce - nonce the proof was requested with
+    ///
+    pub fn verify_bbs_proof<M: AsRef<[u8]>>(
+        &self,
+        message_count: usize,
+        revealed: &[(usize, M)],
+        proof: &[u8],
+        nonce: &[u8],
+    ) -> Result<bool, Error> {
+        match self.key_type {
+            KeyType::Multikey => self
+                .resolve_multikey()?
+                .verify_bbs_proof(message_count, revealed, proof, nonce),
+            KeyType::Bls12381G2Key2020 => {
+                bbs_plus::verify_proof(&self.public_key, message_count, revealed, proof, nonce)
+            }
+            _ => Err(Error::WrongKeyType),
+        }
+    }
+
+    /// Checks proof of possession made by `KeyPair::prove_possession`.
+    /// Supported by `Bls12381G1Key2020` and `Bls12381G2Key2020` keys only.
+    ///
+    /// # Parameters
+    ///
+    /// * proof - proof of possession of the private key of this public key
+    ///
+    pub fn verify_possession(&self, proof: &[u8]) -> Result<bool, Error> {
+        match self.key_type {
+            KeyType::Multikey => self.resolve_multikey()?.verify_possession(proof),
+            KeyType::Bls12381G1Key2020 => {
+                let pk = bls::g1_public_key(&self.public_key)?;
+                Ok(bls::g1_key_possession_proof(proof)?.verify(pk).unwrap_u8() == 1u8)
+            },
+            KeyType::Bls12381G2Key2020 => {
+                let pk = bls::g2_public_key(&self.public_key)?;
+                Ok(bls::g2_key_possession_proof(proof)?.verify(pk).unwrap_u8() == 1u8)
+            },
+            _ => Err(Error::WrongKeyType),
+        }
+    }
 }
 
 /// Lists all supported* keys.
@@ -255,6 +612,16 @@ impl PublicKeyInfo {
 /// `RsaVerificationKey2018`
 /// [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#ecdsasecp256k1recoverysignature2020)
 /// `EcdsaSecp256k1RecoveryMethod2020`
+/// [BIP340](https://github.com/bitcoin/bips/blob/master/bip-0340.mediawiki)
+/// `SchnorrSecp256k1VerificationKey2019`
+/// [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#jsonwebsignature2020)
+/// `EcdsaSecp384r1VerificationKey2019`, `EcdsaSecp521r1VerificationKey2019`
+/// [W3C](https://w3c.github.io/vc-di-eddsa/#ed25519verificationkey2020)
+/// `Ed25519VerificationKey2020`
+/// [W3C](https://w3c-ccg.github.io/ld-cryptosuite-registry/#x25519keyagreementkey2020)
+/// `X25519KeyAgreementKey2020`
+/// [W3C](https://www.w3.org/TR/controller-document/#multikey)
+/// `Multikey`, key type is defined by the multicodec prefix of the key
 #[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
 pub enum KeyType {
     JwsVerificationKey2020,
@@ -266,7 +633,12 @@ pub enum KeyType {
     SchnorrSecp256k1VerificationKey2019,
     EcdsaSecp256k1RecoveryMethod2020,
     Bls12381G1Key2020,
-    Bls12381G2Key2020
+    Bls12381G2Key2020,
+    EcdsaSecp384r1VerificationKey2019,
+    EcdsaSecp521r1VerificationKey2019,
+    Ed25519VerificationKey2020,
+    X25519KeyAgreementKey2020,
+    Multikey,
 }
 
 impl FromStr for KeyType {
@@ -284,6 +656,11 @@ impl FromStr for KeyType {
             "EcdsaSecp256k1RecoveryMethod2020" => Ok(Self::EcdsaSecp256k1RecoveryMethod2020),
             "Bls12381G1Key2020" => Ok(Self::Bls12381G1Key2020),
             "Bls12381G2Key2020" => Ok(Self::Bls12381G2Key2020),
+            "EcdsaSecp384r1VerificationKey2019" => Ok(Self::EcdsaSecp384r1VerificationKey2019),
+            "EcdsaSecp521r1VerificationKey2019" => Ok(Self::EcdsaSecp521r1VerificationKey2019),
+            "Ed25519VerificationKey2020" => Ok(Self::Ed25519VerificationKey2020),
+            "X25519KeyAgreementKey2020" => Ok(Self::X25519KeyAgreementKey2020),
+            "Multikey" => Ok(Self::Multikey),
             _ => Err(Error::UnsupportedKeyType),
         }
     }
@@ -304,6 +681,11 @@ impl TryInto<KeyType> for &str {
             "EcdsaSecp256k1RecoveryMethod2020" => Ok(KeyType::EcdsaSecp256k1RecoveryMethod2020),
             "Bls12381G1Key2020" => Ok(KeyType::Bls12381G1Key2020),
             "Bls12381G2Key2020" => Ok(KeyType::Bls12381G2Key2020),
+            "EcdsaSecp384r1VerificationKey2019" => Ok(KeyType::EcdsaSecp384r1VerificationKey2019),
+            "EcdsaSecp521r1VerificationKey2019" => Ok(KeyType::EcdsaSecp521r1VerificationKey2019),
+            "Ed25519VerificationKey2020" => Ok(KeyType::Ed25519VerificationKey2020),
+            "X25519KeyAgreementKey2020" => Ok(KeyType::X25519KeyAgreementKey2020),
+            "Multikey" => Ok(KeyType::Multikey),
             _ => Err(Error::UnsupportedKeyType),
         }
     }
@@ -322,6 +704,63 @@ pub enum PublicKeyEncoding {
     EthereumAddress(String),
 }
 
+impl PublicKeyEncoding {
+    // format of this encoding
+    fn format(&self) -> PublicKeyFormat {
+        match self {
+            PublicKeyEncoding::PublicKeyHex(_) => PublicKeyFormat::Hex,
+            PublicKeyEncoding::PublicKeyBase64(_) => PublicKeyFormat::Base64,
+            PublicKeyEncoding::PublicKeyBase58(_) => PublicKeyFormat::Base58,
+            PublicKeyEncoding::PublicKeyMultibase(_) => PublicKeyFormat::Multibase,
+            PublicKeyEncoding::EthereumAddress(_) => PublicKeyFormat::EthereumAddress,
+        }
+    }
+}
+
+/// Selects `PublicKeyEncoding` public key is encoded into.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum PublicKeyFormat {
+    Hex,
+    /// URL safe base64
+    Base64,
+    Base58,
+    /// base58btc multibase of multicodec prefixed key
+    Multibase,
+    /// EIP-55 checksummed address of secp256k1 key, can not be decoded
+    EthereumAddress,
+}
+
+// parses PKCS#1 DER encoded RSA public key
+fn rsa_public_key(der: &[u8]) -> Result<rsa::RsaPublicKey, Error> {
+    use rsa::pkcs1::DecodeRsaPublicKey;
+    Ok(rsa::RsaPublicKey::from_pkcs1_der(der).map_err(rsa::errors::Error::from)?)
+}
+
+// EIP-55 checksummed ethereum address of secp256k1 public key
+fn ethereum_address(pki: &PublicKeyInfo) -> Result<String, Error> {
+    use k256::elliptic_curve::sec1::ToEncodedPoint;
+    match pki.key_type {
+        KeyType::EcdsaSecp256k1VerificationKey2019 | KeyType::EcdsaSecp256k1RecoveryMethod2020 => {
+            let pk = k256::PublicKey::from_sec1_bytes(&pki.public_key)
+                .map_err(|_| Error::InvalidPublicKey)?;
+            let point = pk.to_encoded_point(false);
+            let address = hex::encode(&Keccak256::digest(&point.as_bytes()[1..])[12..]);
+            let hash = Keccak256::digest(address.as_bytes());
+            let checksummed: String = address
+                .chars()
+                .enumerate()
+                .map(|(i, c)| {
+                    let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+                    if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+                })
+                .collect();
+            Ok(format!("0x{}", checksummed))
+        }
+        KeyType::Multikey => ethereum_address(&pki.resolve_multikey()?),
+        _ => Err(Error::WrongKeyType),
+    }
+}
+
 // TODO: find out if they still required by any consumer
 // cleanup if not...
 
@@ -329,16 +768,16 @@ pub fn to_recoverable_signature(
     _v: u8,
     r: &[u8; 32],
     s: &[u8; 32],
-) -> Result<recoverable::Signature, Error> {
-    let s_key = SigningKey::random(rand::rngs::OsRng);
+) -> Result<(Signature, RecoveryId), Error> {
+    let s_key = SigningKey::random(&mut rand::rngs::OsRng);
     let mut data = [0u8; 64];
     data[0..32].copy_from_slice(r);
     data[32..64].copy_from_slice(s);
 
-    Ok(s_key.sign(&data))
+    Ok(s_key.sign_prehash_recoverable(&Keccak256::digest(data))?)
 }
 
-pub fn parse_concatenated(signature: &[u8]) -> Result<recoverable::Signature, Error> {
+pub fn parse_concatenated(signature: &[u8]) -> Result<(Signature, RecoveryId), Error> {
     let mut r = [0u8; 32];
     let mut s = [0u8; 32];
     let v = signature[64];
@@ -346,11 +785,6 @@ pub fn parse_concatenated(signature: &[u8]) -> Result<recoverable::Signature, Er
     r.copy_from_slice(&signature[..32]);
     s.copy_from_slice(&signature[32..64]);
 
-    println!("{:?}", signature);
-    println!("{:?}", r);
-    println!("{:?}", s);
-    println!("{:?}", v);
-
     to_recoverable_signature(v, &r, &s)
 }
 
@@ -412,3 +846,151 @@ fn ecdsa_private_public_keys_full_cycle_test() -> Result<(), Error> {
     assert!(&kp.public_key.verify(&message, &sign)?);
     Ok(())
 }
+
+#[test]
+fn schnorr_bip340_verify_vectors() -> Result<(), Error> {
+    // test vectors from https://github.com/bitcoin/bips/blob/master/bip-0340/test-vectors.csv
+    let pki = |pk: &str| PublicKeyInfo::new(
+        KeyType::SchnorrSecp256k1VerificationKey2019,
+        &hex::decode(pk).unwrap());
+    // vector 1
+    assert!(pki("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659").verify_prehashed(
+        &hex::decode("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89").unwrap(),
+        &hex::decode("6896bd60eeae296db48a229ff71dfe071bde413e6d43f917dc8dcf8c78de33418906d11ac976abccb20b091292bff4ea897efcb639ea871cfa95f6de339e4b0a").unwrap())?);
+    // vector 4
+    assert!(pki("d69c3509bb99e412e68b0fe8544e72837dfa30746d8be2aa65975f29d22dc7b9").verify_prehashed(
+        &hex::decode("4df3c3f68fcc83b27e9d42c90431a72499f17875c81a599b566c9889b9696703").unwrap(),
+        &hex::decode("00000000000000000000003b78ce563f89a0ed9414f5aa28ad0d96d6795f9c6376afb1548af603b3eb45c9f8207dee1060cb71c04e80f593060b07d28308d7f4").unwrap())?);
+    // vector 6, R has odd y
+    assert!(!pki("dff1d77f2a671c5f36183726db2341be58feae1da2deced843240f7b502ba659").verify_prehashed(
+        &hex::decode("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89").unwrap(),
+        &hex::decode("fff97bd5755eeea420453a14355235d382f6472f8568a18b2f057a14602975563cc27944640ac607cd107ae10923d9ef7a73c643e166be5ebeafa34b1ac553e2").unwrap())?);
+    // vector 5, public key not on the curve
+    assert!(pki("eefdea4cdb677750a420fee807eacf21eb9898ae79b9768766e4faa04a2d4a34").verify_prehashed(
+        &hex::decode("243f6a8885a308d313198a2e03707344a4093822299f31d0082efa98ec4e6c89").unwrap(),
+        &hex::decode("6cff5c3ba86c69ea4b7376f31a9bcb4f74c1976089b2d9963da2e5543e17776969e89b4c5564d00349106b8497785dd7d1d713a8ae82b32fa79d5f7fc407d39b").unwrap()).is_err());
+    Ok(())
+}
+
+#[test]
+fn public_key_multibase_round_trip() -> Result<(), Error> {
+    let raw = hex::decode("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a").unwrap();
+    let pki = PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2020, &raw)
+        .controller(vec!["did:example:123#key-1".into()]);
+    let json = serde_json::to_value(&pki)?;
+    assert!(json.get("publicKeyHex").is_none());
+    assert!(json["publicKeyMultibase"].as_str().unwrap().starts_with("z6LS"));
+    let parsed: PublicKeyInfo = serde_json::from_value(json)?;
+    assert_eq!(parsed.key_type, KeyType::X25519KeyAgreementKey2020);
+    assert_eq!(parsed.public_key, raw);
+
+    // older suites keep hex
+    let json = serde_json::to_value(PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2019, &raw))?;
+    assert_eq!(json["publicKeyHex"], hex::encode(&raw));
+    assert!(json.get("publicKeyMultibase").is_none());
+
+    // multibase of other key type is rejected
+    let mut wrong = serde_json::to_value(&pki)?;
+    wrong["type"] = "Ed25519VerificationKey2020".into();
+    assert!(serde_json::from_value::<PublicKeyInfo>(wrong).is_err());
+    Ok(())
+}
+
+#[test]
+fn multikey_round_trip_and_verify() -> Result<(), Error> {
+    use crate::contents::key_pair::KeyPair;
+    let kp = KeyPair::random_pair(KeyType::Ed25519VerificationKey2020)?;
+    let signature = kp.sign(b"message")?;
+    let multikey = kp.public_key.to_multikey()?;
+    assert_eq!(multikey.key_type, KeyType::Multikey);
+
+    let json = serde_json::to_value(&multikey)?;
+    assert_eq!(json["type"], "Multikey");
+    assert!(json["publicKeyMultibase"].as_str().unwrap().starts_with("z6Mk"));
+    let parsed: PublicKeyInfo = serde_json::from_value(json)?;
+    assert_eq!(parsed.public_key, multikey.public_key);
+    assert!(parsed.verify(b"message", &signature)?);
+
+    let resolved = parsed.resolve_multikey()?;
+    assert_eq!(resolved.key_type, KeyType::Ed25519VerificationKey2020);
+    assert_eq!(resolved.public_key, kp.public_key.public_key);
+    assert!(PublicKeyInfo::new(KeyType::SchnorrSecp256k1VerificationKey2019, &[0u8; 32])
+        .to_multikey()
+        .is_err());
+    Ok(())
+}
+
+#[test]
+fn public_key_encodings_round_trip() -> Result<(), Error> {
+    let raw = hex::decode("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a").unwrap();
+    let pki = PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2019, &raw);
+    let formats = [
+        (PublicKeyFormat::Hex, "publicKeyHex"),
+        (PublicKeyFormat::Base64, "publicKeyBase64"),
+        (PublicKeyFormat::Base58, "publicKeyBase58"),
+        (PublicKeyFormat::Multibase, "publicKeyMultibase"),
+    ];
+    for (format, field) in formats.iter() {
+        let json = serde_json::to_value(pki.clone().encoding(*format))?;
+        assert!(json.get(field).is_some());
+        assert_eq!(json.as_object().unwrap().len(), 3);
+        let parsed: PublicKeyInfo = serde_json::from_value(json.clone())?;
+        assert_eq!(parsed.public_key, raw);
+        assert_eq!(parsed.get_encoding(), Some(*format));
+        // encoding is kept
+        assert_eq!(serde_json::to_value(&parsed)?, json);
+    }
+    let base58 = r#"{"controller":[],"type":"X25519KeyAgreementKey2019","publicKeyBase58":"9xgMXw7nrN39BoN9rJuGV6B9LwBNYXAJAMfeACcdyLMP"}"#;
+    assert_eq!(serde_json::from_str::<PublicKeyInfo>(base58)?.public_key, raw);
+    assert!(pki.encode(PublicKeyFormat::EthereumAddress).is_err());
+    Ok(())
+}
+
+#[test]
+fn ethereum_address_is_output_only() -> Result<(), Error> {
+    use crate::contents::key_pair::KeyPair;
+    // address of private key 1
+    let mut sk = [0u8; 32];
+    sk[31] = 1;
+    let kp = KeyPair::new(KeyType::EcdsaSecp256k1VerificationKey2019, &sk)?;
+    let address = PublicKeyEncoding::EthereumAddress(
+        "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf".into());
+    assert_eq!(kp.public_key.encode(PublicKeyFormat::EthereumAddress)?, address);
+
+    let json = serde_json::to_value(kp.public_key.clone().encoding(PublicKeyFormat::EthereumAddress))?;
+    assert_eq!(json["ethereumAddress"], "0x7E5F4552091A69125d5DfCb7b8C2659029395Bdf");
+    assert!(serde_json::from_value::<PublicKeyInfo>(json).is_err());
+    assert!(matches!(
+        PublicKeyInfo::from_encoding(KeyType::EcdsaSecp256k1VerificationKey2019, &address),
+        Err(Error::OutputOnlyEncoding)
+    ));
+    Ok(())
+}
+
+#[test]
+fn encrypt_rejects_malformed_x25519_key() -> Result<(), Error> {
+    for len in [0, 31, 33].iter() {
+        assert!(matches!(
+            PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2019, &vec![9u8; *len])
+                .encrypt(b"secret", None),
+            Err(Error::InvalidPublicKey)
+        ));
+    }
+    Ok(())
+}
+
+#[test]
+fn secp256k1_verify_rejects_short_signature() -> Result<(), Error> {
+    use crate::contents::key_pair::KeyPair;
+    for key_type in [
+        KeyType::EcdsaSecp256k1VerificationKey2019,
+        KeyType::EcdsaSecp256k1RecoveryMethod2020,
+    ] {
+        let key_pair = KeyPair::random_pair(key_type)?;
+        let signature = key_pair.sign(b"message")?;
+        assert!(key_pair.public_key.verify(b"message", &signature)?);
+        assert!(key_pair.public_key.verify(b"message", &signature[..32]).is_err());
+        assert!(key_pair.public_key.verify(b"message", &[]).is_err());
+    }
+    Ok(())
+}
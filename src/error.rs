@@ -12,6 +12,22 @@ pub enum Error {
     WrongKeyType,
     #[error("key size incorrect")]
     WrongKeyLength,
+    /// Public key bytes are not a valid point of the key's curve
+    #[error("public key encoding invalid")]
+    InvalidPublicKey,
+    /// Private key bytes do not match their declared encoding
+    #[error("private key encoding invalid")]
+    InvalidPrivateKey,
+    /// Private key is kept outside of the `KeyPair`, in a KMS, secure enclave
+    /// or is derived from the wallet's seed
+    #[error("private key is not available")]
+    PrivateKeyUnavailable,
+    /// Encoding can be produced, but not decoded back into the key
+    #[error("public key encoding is output only")]
+    OutputOnlyEncoding,
+    /// Message must be hashed to the size required by the signature scheme
+    #[error("message size incorrect")]
+    WrongMessageLength,
     /// No key found
     #[error("key not found")]
     KeyNotFound,
@@ -32,10 +48,66 @@ pub enum Error {
     AeadCryptoError(aead::Error),
     #[error(transparent)]
     EcdsaCryptoError(#[from] k256::ecdsa::Error),
+    /// Opaque errors wrapper for p521 crate, mapped explicitly
+    /// as it shares the ecdsa error type with k256
+    #[error(transparent)]
+    P521CryptoError(p521::ecdsa::Error),
+    /// Opaque errors wrapper for rsa crate
+    #[error(transparent)]
+    RsaCryptoError(#[from] rsa::errors::Error),
     #[error("cryptography failure in ed25519: {0}")]
     EdCryptoError(ed25519_dalek::ed25519::Error),
     #[error("cryptography failure in BBS/BLS module: {0}")]
     BlsCryptoError(String),
+    /// Password key derivation failure
+    #[error("key derivation failure: {0}")]
+    KdfError(String),
+    /// Password key derivation parameter exceeds its upper bound
+    #[error("key derivation parameter {0} out of range")]
+    KdfParameterOutOfRange(&'static str),
+    /// Malformed hierarchical derivation path
+    #[error("invalid derivation path {0}")]
+    InvalidDerivationPath(String),
+    /// BIP39 mnemonic can not be generated or parsed
+    #[error("mnemonic failure: {0}")]
+    MnemonicError(String),
+    /// Key derived from the seed differs from the stored public key
+    #[error("derived key does not match stored public key")]
+    DerivedKeyMismatch,
+    /// Key derivation function can only be used to unlock existing wallets
+    #[error("legacy key derivation function can not be used for locking")]
+    LegacyKdf,
+    /// Locked wallet envelope is shorter than its declared fields
+    #[error("locked wallet envelope is truncated")]
+    EnvelopeTruncated,
+    /// Data is not a locked wallet envelope
+    #[error("unknown locked wallet envelope format")]
+    UnknownEnvelopeFormat,
+    /// Envelope produced by newer version of the library
+    #[error("unsupported locked wallet envelope version {0}")]
+    UnsupportedEnvelopeVersion(u8),
+    /// Envelope field does not fit its length prefix
+    #[error("locked wallet envelope field {0} is too long")]
+    EnvelopeFieldTooLong(&'static str),
+    /// Envelope holds more wrapped keys of one kind than allowed
+    #[error("locked wallet envelope has too many {0} keys")]
+    TooManyWrappedKeys(&'static str),
+    #[error("unknown key derivation function {0}")]
+    UnknownKdf(u8),
+    #[error("unknown cipher {0}")]
+    UnknownCipher(u8),
+    /// Shamir shares can not be combined
+    #[error("recovery share failure: {0}")]
+    RecoveryShareError(String),
+    /// Recovery share is encrypted to a guardian and must be unsealed first
+    #[error("recovery share is sealed")]
+    RecoveryShareSealed,
+    /// Stored wallet was modified since it was loaded
+    #[error("wallet {0} was modified concurrently")]
+    StoreConflict(String),
+    /// No wallet with such id in the store
+    #[error("wallet with id {0} not found in the store")]
+    WalletNotFound(String),
     /// Opaque errors wrapper for secp256k1 crate
     /// #Transparent errors
     ///
@@ -48,6 +120,12 @@ pub enum Error {
     /// base64 decode errore transparent propagation
     #[error(transparent)]
     Base64DecodeError(#[from] base64::DecodeError),
+    /// sled database errors
+    #[error(transparent)]
+    StoreError(#[from] sled::Error),
+    /// File system errors
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
     /// Other errors implementing `std::error::Error`
     #[error(transparent)]
     Other(#[from] Box<dyn std::error::Error>),
@@ -0,0 +1,425 @@
+use crate::{get_random, Error};
+use chacha20poly1305::Key;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Sha3_256};
+use std::convert::TryFrom;
+use zeroize::Zeroizing;
+
+/// Size of the random salt generated for every lock
+pub const SALT_SIZE: usize = 16;
+
+/// Upper bound of memory used by `Kdf::Argon2id` and `Kdf::Scrypt`, 256 MiB.
+/// Parameters are read from the locked wallet, so they are bounded
+/// to keep crafted wallets from exhausting memory or CPU.
+pub const MAX_MEMORY_BYTES: u64 = 1 << 28;
+/// Upper bound of `Kdf::Argon2id` passes
+pub const MAX_ITERATIONS: u32 = 16;
+/// Upper bound of `Kdf::Argon2id` parallelism and `Kdf::Scrypt` parallelization
+pub const MAX_PARALLELISM: u32 = 16;
+/// Upper bound of `Kdf::Scrypt` log2 of N
+pub const MAX_LOG_N: u8 = 20;
+/// Upper bound of `Kdf::Scrypt` block size
+pub const MAX_BLOCK_SIZE: u32 = 32;
+
+/// Password based key derivation function used to turn wallet
+/// password into XChaCha20Poly1305 key.
+/// Parameters are stored along with the `LockedWallet`, so they can
+/// be changed without breaking already locked wallets.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "algorithm", rename_all = "camelCase")]
+pub enum Kdf {
+    /// Unsalted SHA3-256 hash of the password.
+    /// Used by wallets locked before key derivation became configurable;
+    /// can only be used to unlock.
+    Sha3,
+    /// Argon2id [RFC 9106](https://www.rfc-editor.org/rfc/rfc9106.html)
+    #[serde(rename_all = "camelCase")]
+    Argon2id {
+        /// memory size in KiB
+        memory: u32,
+        /// number of passes
+        iterations: u32,
+        /// degree of parallelism
+        parallelism: u32,
+    },
+    /// scrypt [RFC 7914](https://www.rfc-editor.org/rfc/rfc7914.html)
+    #[serde(rename_all = "camelCase")]
+    Scrypt {
+        /// log2 of the CPU/memory cost parameter N
+        log_n: u8,
+        /// block size
+        r: u32,
+        /// parallelization
+        p: u32,
+    },
+}
+
+impl Kdf {
+    /// Argon2id with 19 MiB of memory and 2 passes,
+    /// minimal configuration recommended by OWASP
+    pub fn argon2id() -> Self {
+        Kdf::Argon2id {
+            memory: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+
+    /// scrypt with N = 2^15, r = 8 and p = 1
+    pub fn scrypt() -> Self {
+        Kdf::Scrypt {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+
+    /// Generates salt suitable for this function
+    pub fn new_salt(&self) -> Result<Vec<u8>, Error> {
+        match self {
+            Kdf::Sha3 => Ok(vec![]),
+            _ => get_random(SALT_SIZE),
+        }
+    }
+
+    /// Checks parameters against `MAX_MEMORY_BYTES` and other upper bounds.
+    /// Fails with `Error::KdfParameterOutOfRange` naming the parameter.
+    pub fn check_bounds(&self) -> Result<(), Error> {
+        let out_of_range = match *self {
+            Kdf::Sha3 => None,
+            Kdf::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => {
+                if memory as u64 * 1024 > MAX_MEMORY_BYTES {
+                    Some("memory")
+                } else if iterations > MAX_ITERATIONS {
+                    Some("iterations")
+                } else if parallelism > MAX_PARALLELISM {
+                    Some("parallelism")
+                } else {
+                    None
+                }
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                if log_n > MAX_LOG_N {
+                    Some("log_n")
+                } else if r > MAX_BLOCK_SIZE || 128 * r as u64 * (1 << log_n) > MAX_MEMORY_BYTES {
+                    Some("r")
+                } else if p > MAX_PARALLELISM {
+                    Some("p")
+                } else {
+                    None
+                }
+            }
+        };
+        match out_of_range {
+            Some(name) => Err(Error::KdfParameterOutOfRange(name)),
+            None => Ok(()),
+        }
+    }
+
+    /// Derives encryption key from the password.
+    /// Parameters are checked with `check_bounds` first.
+    /// Key is wiped from memory when dropped.
+    ///
+    /// # Parameters
+    ///
+    /// * password - secret provided by the user
+    /// * salt - salt stored with the locked wallet
+    ///
+    pub fn derive_key(&self, password: &[u8], salt: &[u8]) -> Result<Zeroizing<Key>, Error> {
+        self.check_bounds()?;
+        let mut key = Zeroizing::new(Key::default());
+        match *self {
+            Kdf::Sha3 => {
+                let mut sha3 = Sha3_256::new();
+                sha3.update(password);
+                key.copy_from_slice(&sha3.finalize());
+            }
+            Kdf::Argon2id {
+                memory,
+                iterations,
+                parallelism,
+            } => {
+                use argon2::{Algorithm, Argon2, Params, Version};
+                let params = Params::new(memory, iterations, parallelism, Some(key.len()))
+                    .map_err(|e| Error::KdfError(e.to_string()))?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(password, salt, &mut key)
+                    .map_err(|e| Error::KdfError(e.to_string()))?;
+            }
+            Kdf::Scrypt { log_n, r, p } => {
+                let params =
+                    scrypt::Params::new(log_n, r, p).map_err(|e| Error::KdfError(e.to_string()))?;
+                scrypt::scrypt(password, salt, &params, &mut key)
+                    .map_err(|e| Error::KdfError(e.to_string()))?;
+            }
+        }
+        Ok(key)
+    }
+}
+
+/// Defaults to `Kdf::argon2id()`
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::argon2id()
+    }
+}
+
+// scrypt with minimal cost, so tests locking wallets run fast
+#[cfg(test)]
+pub(crate) fn test_kdf() -> Kdf {
+    Kdf::Scrypt {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    }
+}
+
+/// Key derivation function turning ECDH shared secret into symmetric key,
+/// along with the context the key is bound to,
+/// see `UnlockedWallet::derive_shared_key`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "algorithm", rename_all = "camelCase")]
+pub enum KeyAgreementKdf {
+    /// HKDF with SHA-256 [RFC 5869](https://www.rfc-editor.org/rfc/rfc5869.html)
+    HkdfSha256 {
+        /// salt, empty if not used
+        salt: Vec<u8>,
+        /// context and application specific information
+        info: Vec<u8>,
+    },
+    /// Concat KDF with SHA-256 as profiled by JOSE ECDH-ES
+    /// [RFC 7518](https://www.rfc-editor.org/rfc/rfc7518.html#section-4.6.2).
+    /// OtherInfo is `AlgorithmID || PartyUInfo || PartyVInfo || SuppPubInfo`,
+    /// each of the first three prefixed with its 32 bit length and SuppPubInfo
+    /// being keydatalen, the length of the derived key in bits.
+    #[serde(rename_all = "camelCase")]
+    ConcatKdf {
+        /// AlgorithmID, value of the `enc` header for direct key agreement
+        /// or of the `alg` header for key wrapping, e.g. `A256GCM`
+        algorithm_id: Vec<u8>,
+        /// PartyUInfo, value of the `apu` header
+        party_u_info: Vec<u8>,
+        /// PartyVInfo, value of the `apv` header
+        party_v_info: Vec<u8>,
+    },
+}
+
+impl KeyAgreementKdf {
+    /// Derives key of `len` bytes from the shared secret.
+    /// Key is wiped from memory when dropped.
+    ///
+    /// # Parameters
+    ///
+    /// * secret - ECDH shared secret
+    /// * len - length of the key in bytes, keydatalen of Concat KDF in bytes
+    ///
+    pub fn derive_key(&self, secret: &[u8], len: usize) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if len == 0 {
+            return Err(Error::KdfError("key length is zero".into()));
+        }
+        let mut key = Zeroizing::new(vec![0u8; len]);
+        match self {
+            KeyAgreementKdf::HkdfSha256 { salt, info } => {
+                hkdf::Hkdf::<sha2::Sha256>::new(Some(salt), secret)
+                    .expand(info, &mut key)
+                    .map_err(|e| Error::KdfError(e.to_string()))?;
+            }
+            KeyAgreementKdf::ConcatKdf {
+                algorithm_id,
+                party_u_info,
+                party_v_info,
+            } => {
+                // SuppPubInfo is keydatalen in bits
+                let key_bits = u32::try_from(len * 8)
+                    .map_err(|_| Error::KdfError("key length is too big".into()))?;
+                let other_info = [
+                    length_prefixed(algorithm_id),
+                    length_prefixed(party_u_info),
+                    length_prefixed(party_v_info),
+                    key_bits.to_be_bytes().to_vec(),
+                ]
+                .concat();
+                concat_kdf::derive_key_into::<sha2::Sha256>(secret, &other_info, &mut key)
+                    .map_err(|e| Error::KdfError(e.to_string()))?;
+            }
+        }
+        Ok(key)
+    }
+}
+
+// data prefixed with its 32 bit big endian length, as in JWA OtherInfo
+fn length_prefixed(data: &[u8]) -> Vec<u8> {
+    [&(data.len() as u32).to_be_bytes()[..], data].concat()
+}
+
+#[test]
+fn argon2id_test_vector() -> Result<(), Error> {
+    // test vector from https://www.rfc-editor.org/rfc/rfc9106.html#section-5.3
+    // for the Argon2id v1.3 configuration used by `Kdf::Argon2id`
+    use argon2::{Algorithm, Argon2, ParamsBuilder, Version};
+    let to_kdf_error = |e: argon2::Error| Error::KdfError(e.to_string());
+    let mut builder = ParamsBuilder::new();
+    builder
+        .m_cost(32)
+        .and_then(|b| b.t_cost(3))
+        .and_then(|b| b.p_cost(4))
+        .and_then(|b| b.data(&[4u8; 12]))
+        .and_then(|b| b.output_len(32))
+        .map_err(to_kdf_error)?;
+    let params = builder.params().map_err(to_kdf_error)?;
+    let mut tag = [0u8; 32];
+    Argon2::new_with_secret(&[3u8; 8], Algorithm::Argon2id, Version::V0x13, params)
+        .and_then(|argon2| argon2.hash_password_into(&[1u8; 32], &[2u8; 16], &mut tag))
+        .map_err(to_kdf_error)?;
+    assert_eq!(
+        hex::encode(tag),
+        "0d640df58d78766c08c037a34a8b53c9d01ef0452d75b65eb52520e96b01e659"
+    );
+    Ok(())
+}
+
+#[test]
+fn argon2id_derive_key_regression() -> Result<(), Error> {
+    // regression value, not an RFC vector: `Kdf` takes no secret or
+    // associated data, so RFC 9106 inputs give a different tag without them
+    let kdf = Kdf::Argon2id {
+        memory: 32,
+        iterations: 3,
+        parallelism: 4,
+    };
+    let key = kdf.derive_key(&[1u8; 32], &[2u8; 16])?;
+    assert_eq!(
+        hex::encode(key.as_slice()),
+        "03aab965c12001c9d7d0d2de33192c0494b684bb148196d73c1df1acaf6d0c2e"
+    );
+    Ok(())
+}
+
+#[test]
+fn scrypt_test_vector() -> Result<(), Error> {
+    // test vector from https://www.rfc-editor.org/rfc/rfc7914.html#section-12
+    // truncated to the key size
+    let kdf = Kdf::Scrypt {
+        log_n: 10,
+        r: 8,
+        p: 16,
+    };
+    let key = kdf.derive_key(b"password", b"NaCl")?;
+    assert_eq!(
+        hex::encode(key.as_slice()),
+        "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162"
+    );
+    Ok(())
+}
+
+#[test]
+fn parameters_out_of_range_are_rejected() -> Result<(), Error> {
+    let out_of_range = [
+        (
+            Kdf::Argon2id {
+                memory: 2 * 1024 * 1024,
+                iterations: 2,
+                parallelism: 1,
+            },
+            "memory",
+        ),
+        (
+            Kdf::Argon2id {
+                memory: 32,
+                iterations: u32::MAX,
+                parallelism: 1,
+            },
+            "iterations",
+        ),
+        (
+            Kdf::Argon2id {
+                memory: 32,
+                iterations: 2,
+                parallelism: 1024,
+            },
+            "parallelism",
+        ),
+        (
+            Kdf::Scrypt {
+                log_n: 63,
+                r: 8,
+                p: 1,
+            },
+            "log_n",
+        ),
+        (
+            Kdf::Scrypt {
+                log_n: 20,
+                r: 16,
+                p: 1,
+            },
+            "r",
+        ),
+        (
+            Kdf::Scrypt {
+                log_n: 4,
+                r: 8,
+                p: u32::MAX,
+            },
+            "p",
+        ),
+    ];
+    for (kdf, name) in out_of_range.iter() {
+        assert!(matches!(
+            kdf.derive_key(b"password", &[0u8; SALT_SIZE]),
+            Err(Error::KdfParameterOutOfRange(n)) if n == *name
+        ));
+    }
+    Kdf::argon2id().check_bounds()?;
+    Kdf::scrypt().check_bounds()
+}
+
+#[test]
+fn salt_is_random() -> Result<(), Error> {
+    let kdf = Kdf::default();
+    assert_eq!(kdf.new_salt()?.len(), SALT_SIZE);
+    assert_ne!(kdf.new_salt()?, kdf.new_salt()?);
+    assert!(Kdf::Sha3.new_salt()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn hkdf_sha256_test_vector() -> Result<(), Error> {
+    // test case 1 from https://www.rfc-editor.org/rfc/rfc5869.html#appendix-A.1
+    let kdf = KeyAgreementKdf::HkdfSha256 {
+        salt: hex::decode("000102030405060708090a0b0c").unwrap(),
+        info: hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap(),
+    };
+    let key = kdf.derive_key(&[0x0b; 22], 42)?;
+    assert_eq!(
+        hex::encode(key.as_slice()),
+        "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+    );
+    assert!(kdf.derive_key(&[0x0b; 22], 255 * 32 + 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn concat_kdf_test_vector() -> Result<(), Error> {
+    // ECDH-ES example of https://www.rfc-editor.org/rfc/rfc7518.html#appendix-C
+    let z = [
+        158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49, 110,
+        163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+    ];
+    let kdf = KeyAgreementKdf::ConcatKdf {
+        algorithm_id: b"A128GCM".to_vec(),
+        party_u_info: b"Alice".to_vec(),
+        party_v_info: b"Bob".to_vec(),
+    };
+    let key = kdf.derive_key(&z, 16)?;
+    assert_eq!(
+        base64::encode_config(key.as_slice(), base64::URL_SAFE_NO_PAD),
+        "VqqN6vgjbSBcIijNcacQGg"
+    );
+    assert!(kdf.derive_key(&z, 0).is_err());
+    Ok(())
+}
@@ -1,7 +1,5 @@
 use rand_core::{OsRng, RngCore};
 
-#[macro_use]
-extern crate arrayref;
 extern crate aead;
 extern crate chacha20poly1305;
 extern crate ed25519_dalek;
@@ -9,16 +7,22 @@ extern crate thiserror;
 
 pub mod contents;
 mod error;
+pub mod kdf;
 pub mod locked;
+pub mod store;
 pub mod unlocked;
 
 pub mod prelude {
     pub use crate::contents::{
-        key_pair::KeyPair,
-        public_key_info::{to_recoverable_signature, KeyType, PublicKeyInfo},
-        Content, ContentEntity,
+        key_pair::{KeyPair, PrivateKeyEncoding, PrivateKeyFormat},
+        public_key_info::{
+            to_recoverable_signature, KeyType, PublicKeyEncoding, PublicKeyFormat, PublicKeyInfo,
+        },
+        Content, ContentEntity, ExportMode,
     };
-    pub use crate::locked::LockedWallet;
+    pub use crate::kdf::{Kdf, KeyAgreementKdf};
+    pub use crate::locked::{LockedWallet, RecoveryShare};
+    pub use crate::store::{FileStore, MemoryStore, SledStore, WalletStore};
     pub use crate::unlocked::UnlockedWallet;
 }
 #[cfg(feature = "didcomm")]
@@ -36,6 +40,7 @@ pub fn get_random(bytes: usize) -> Result<Vec<u8>, Error> {
 #[cfg(test)]
 mod tests {
     use super::error::Error;
+    use crate::kdf::test_kdf;
     use crate::prelude::*;
 
     #[test]
@@ -71,4 +76,382 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn wallet_scrypt() -> Result<(), Error> {
+        let mut w = UnlockedWallet::new("scrypt wallet");
+        w.new_key(KeyType::Ed25519VerificationKey2018, None)?;
+
+        // cheap parameters to keep the test fast
+        let kdf = Kdf::Scrypt {
+            log_n: 10,
+            r: 8,
+            p: 1,
+        };
+        let lw = w.lock_with(b"My Password", kdf)?;
+        assert_eq!(lw.keys.len(), 1);
+        assert!(lw.unlock(b"Not My Password").is_err());
+        assert_eq!(1, lw.unlock(b"My Password")?.get_keys().len());
+
+        assert!(matches!(
+            w.lock_with(b"My Password", Kdf::Sha3),
+            Err(Error::LegacyKdf)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn unlocked_wallet_rekey() -> Result<(), Error> {
+        let kdf = test_kdf();
+        let mut w = UnlockedWallet::new("rekeyed wallet");
+        let stored = w.lock_with(b"old", kdf)?;
+        w.new_key(KeyType::Ed25519VerificationKey2018, None)?;
+
+        assert!(w.rekey(&stored, b"wrong", b"new", kdf).is_err());
+        let rekeyed = w.rekey(&stored, b"old", b"new", kdf)?;
+        assert_eq!(1, rekeyed.unlock(b"new")?.get_keys().len());
+        assert!(rekeyed.unlock(b"old").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn export_modes_and_redacted_debug() -> Result<(), Error> {
+        let mut w = UnlockedWallet::new("exported wallet");
+        let kp = KeyPair::new(KeyType::Ed25519VerificationKey2018, &[7u8; 32])?;
+        let private_hex = hex::encode(kp.private_key());
+        w.import_content(&Content::KeyPair(kp));
+        let entropy: Content =
+            serde_json::from_str(r#"{"type":["Entropy"],"value":"c2VjcmV0IHNlZWQ="}"#)?;
+        w.import_content(&entropy);
+
+        let public = w.export(ExportMode::PublicOnly)?;
+        assert!(!public.contains(&private_hex));
+        assert!(!public.contains("c2VjcmV0IHNlZWQ="));
+        assert!(public.contains("publicKeyHex"));
+
+        let full = w.export(ExportMode::Full)?;
+        assert!(full.contains(&private_hex));
+        assert!(full.contains("c2VjcmV0IHNlZWQ="));
+
+        let debug = format!("{:?}", w);
+        assert!(!debug.contains(&private_hex));
+        assert!(!debug.contains("c2VjcmV0IHNlZWQ="));
+        Ok(())
+    }
+
+    #[test]
+    fn keys_from_seed_are_resolved() -> Result<(), Error> {
+        // BIP32 test vector 1 seed and its m/0'/1 key
+        let json = r#"{
+            "@context": [],
+            "id": "seeded wallet",
+            "type": ["UniversalWallet2020"],
+            "contents": [
+                {"id": "seed", "type": ["Entropy"], "value": "AAECAwQFBgcICQoLDA0ODw=="},
+                {
+                    "id": "derived",
+                    "controller": [],
+                    "type": "EcdsaSecp256k1VerificationKey2019",
+                    "publicKeyHex": "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+                    "privateKeyFromSeed": {"path": "m/0'/1", "seedRef": "seed"}
+                },
+                {
+                    "id": "mismatched",
+                    "controller": [],
+                    "type": "EcdsaSecp256k1VerificationKey2019",
+                    "publicKeyHex": "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+                    "privateKeyFromSeed": {"path": "m/0'/2", "seedRef": "seed"}
+                },
+                {
+                    "id": "orphan",
+                    "controller": [],
+                    "type": "EcdsaSecp256k1VerificationKey2019",
+                    "publicKeyHex": "03501e454bf00751f24b1b489aa925215d66af2234e3891c3b21a52bedb3cd711c",
+                    "privateKeyFromSeed": {"path": "m/0'/1", "seedRef": "missing"}
+                }
+            ]
+        }"#;
+        let w: UnlockedWallet = serde_json::from_str(json)?;
+        let sig = w.sign_raw("derived", b"message")?;
+        let pk = match w.get_key("derived").unwrap().content {
+            Content::PublicKey(pk) => pk,
+            _ => unreachable!(),
+        };
+        assert!(pk.verify(b"message", &sig)?);
+        assert!(matches!(
+            w.sign_raw("mismatched", b"message"),
+            Err(Error::DerivedKeyMismatch)
+        ));
+        assert!(matches!(
+            w.sign_raw("orphan", b"message"),
+            Err(Error::ContentNotFound(_))
+        ));
+
+        // seed reference is exported, not the derived key
+        let full = w.export(ExportMode::Full)?;
+        assert_eq!(full.matches("privateKeyFromSeed").count(), 3);
+        assert!(!full.contains("privateKeyHex"));
+        Ok(())
+    }
+
+    #[test]
+    fn keys_derived_from_mnemonic() -> Result<(), Error> {
+        use crate::contents::derivation::bip44_path;
+        let kdf = test_kdf();
+        let mut w = UnlockedWallet::new("hd wallet");
+        let seed_ref = w.import_mnemonic(
+            "abandon abandon abandon abandon abandon abandon \
+             abandon abandon abandon abandon abandon about",
+        )?;
+        let derived = w.derive_key(
+            &seed_ref,
+            KeyType::EcdsaSecp256k1RecoveryMethod2020,
+            &bip44_path(60, 0, 0, 0),
+            None,
+        )?;
+        let pk = match derived.content {
+            Content::PublicKey(pk) => pk,
+            _ => unreachable!(),
+        };
+        // well known first ethereum account of the mnemonic
+        assert_eq!(
+            pk.encode(PublicKeyFormat::EthereumAddress)?,
+            PublicKeyEncoding::EthereumAddress("0x9858EfFD232B4033E47d90003D41EC34EcaEda94".into())
+        );
+
+        // only the path is persisted, key is derived again after unlock
+        let unlocked = w.lock_with(b"password", kdf)?.unlock(b"password")?;
+        assert!(pk.verify(b"message", &unlocked.sign_raw(&derived.id, b"message")?)?);
+        let full = unlocked.export(ExportMode::Full)?;
+        assert!(full.contains("privateKeyFromSeed"));
+        assert!(!full.contains("privateKeyHex"));
+
+        let (_, phrase) = w.new_mnemonic(12)?;
+        let mut restored = UnlockedWallet::new("restored wallet");
+        let restored_ref = restored.import_mnemonic(&phrase)?;
+        assert!(restored
+            .derive_key(
+                &restored_ref,
+                KeyType::Ed25519VerificationKey2018,
+                "m/0",
+                None
+            )
+            .is_err());
+        assert!(matches!(
+            restored.derive_key(
+                "missing",
+                KeyType::EcdsaSecp256k1VerificationKey2019,
+                "m",
+                None
+            ),
+            Err(Error::ContentNotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ed25519_and_x25519_keys_from_seed() -> Result<(), Error> {
+        use crate::contents::entropy::Entropy;
+        let kdf = test_kdf();
+        let mut w = UnlockedWallet::new("didcomm identity");
+        // SLIP-0010 test vector 1 seed
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let seed_ref = w
+            .import_content(&Content::Entropy(Entropy::new(&seed)))
+            .unwrap()
+            .id;
+        let signing = w.derive_key(&seed_ref, KeyType::Ed25519VerificationKey2018, "m", None)?;
+        let agreement =
+            w.derive_key(&seed_ref, KeyType::X25519KeyAgreementKey2019, "m/0'", None)?;
+        let (signing_pk, agreement_pk) = match (signing.content, agreement.content) {
+            (Content::PublicKey(s), Content::PublicKey(a)) => (s, a),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            hex::encode(&signing_pk.public_key),
+            "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed"
+        );
+
+        let unlocked = w.lock_with(b"password", kdf)?.unlock(b"password")?;
+        assert!(signing_pk.verify(b"message", &unlocked.sign_raw(&signing.id, b"message")?)?);
+        let peer = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+        assert_eq!(
+            unlocked.ecdh_key_agreement(&agreement.id, &peer.public_key.public_key)?,
+            peer.ecdh_key_agreement(&agreement_pk.public_key)?
+        );
+        let cipher = agreement_pk.encrypt(b"secret", None)?;
+        assert_eq!(unlocked.decrypt(&agreement.id, &cipher, None)?, b"secret");
+        Ok(())
+    }
+
+    #[test]
+    fn multikey_keys_from_seed_are_resolved() -> Result<(), Error> {
+        use crate::contents::entropy::Entropy;
+        let kdf = test_kdf();
+        let mut w = UnlockedWallet::new("multikey wallet");
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let seed_ref = w
+            .import_content(&Content::Entropy(Entropy::new(&seed)))
+            .unwrap()
+            .id;
+        let kp = KeyPair::derive(KeyType::Ed25519VerificationKey2020, &seed, "m/0'")?
+            .to_multikey()?
+            .encoding(PrivateKeyFormat::FromSeed {
+                path: "m/0'".into(),
+                seed_ref: seed_ref.clone(),
+            });
+        let pk = kp.public_key.clone();
+        assert_eq!(pk.key_type, KeyType::Multikey);
+        let key_ref = w.import_content(&Content::KeyPair(kp)).unwrap().id;
+
+        // private key is derived as Ed25519 one after unlock
+        let unlocked = w.lock_with(b"password", kdf)?.unlock(b"password")?;
+        assert!(pk.verify(b"message", &unlocked.sign_raw(&key_ref, b"message")?)?);
+        assert!(!unlocked
+            .export(ExportMode::Full)?
+            .contains("privateKeyMultibase"));
+        Ok(())
+    }
+
+    #[test]
+    fn wallet_ecdh_validates_peer_keys() -> Result<(), Error> {
+        let mut w = UnlockedWallet::new("ecdh wallet");
+        for kt in [
+            KeyType::X25519KeyAgreementKey2019,
+            KeyType::EcdsaSecp256k1VerificationKey2019,
+            KeyType::JwsVerificationKey2020,
+        ]
+        .iter()
+        {
+            let key_ref = w.new_key(*kt, None)?.id;
+            let peer = KeyPair::random_pair(*kt)?;
+            let own_pk = match w.get_key(&key_ref).unwrap().content {
+                Content::PublicKey(pk) => pk,
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                w.ecdh_key_agreement(&key_ref, &peer.public_key.public_key)?,
+                peer.ecdh_key_agreement(&own_pk.public_key)?
+            );
+            for invalid in [&[][..], &[2u8; 5], &peer.public_key.public_key[1..]].iter() {
+                assert!(matches!(
+                    w.ecdh_key_agreement(&key_ref, invalid),
+                    Err(Error::InvalidPublicKey)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn shared_keys_derived_in_wallets() -> Result<(), Error> {
+        let mut alice = UnlockedWallet::new("alice");
+        let mut bob = UnlockedWallet::new("bob");
+        let public_key = |w: &UnlockedWallet, id: &str| match w.get_key(id).unwrap().content {
+            Content::PublicKey(pk) => pk.public_key,
+            _ => unreachable!(),
+        };
+        for kt in [
+            KeyType::X25519KeyAgreementKey2020,
+            KeyType::EcdsaSecp256k1VerificationKey2019,
+        ]
+        .iter()
+        {
+            let alice_ref = alice.new_key(*kt, None)?.id;
+            let bob_ref = bob.new_key(*kt, None)?.id;
+            let (alice_pk, bob_pk) = (public_key(&alice, &alice_ref), public_key(&bob, &bob_ref));
+            let kdfs = |algorithm: &[u8]| {
+                [
+                    KeyAgreementKdf::HkdfSha256 {
+                        salt: b"salt".to_vec(),
+                        info: algorithm.to_vec(),
+                    },
+                    KeyAgreementKdf::ConcatKdf {
+                        algorithm_id: algorithm.to_vec(),
+                        party_u_info: b"Alice".to_vec(),
+                        party_v_info: b"Bob".to_vec(),
+                    },
+                ]
+            };
+            let (a256, a128) = (kdfs(b"A256GCM"), kdfs(b"A128GCM"));
+            for (kdf, other) in a256.iter().zip(a128.iter()) {
+                let key = alice.derive_shared_key(&alice_ref, &bob_pk, kdf, 32)?;
+                assert_eq!(key.len(), 32);
+                assert_eq!(key, bob.derive_shared_key(&bob_ref, &alice_pk, kdf, 32)?);
+                assert_ne!(key, bob.derive_shared_key(&bob_ref, &alice_pk, other, 32)?);
+                assert_ne!(key, alice.ecdh_key_agreement(&alice_ref, &bob_pk)?);
+            }
+            assert_ne!(
+                alice.derive_shared_key(&alice_ref, &bob_pk, &a256[0], 32)?,
+                alice.derive_shared_key(&alice_ref, &bob_pk, &a256[1], 32)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn concat_kdf_shared_key_test_vector() -> Result<(), Error> {
+        // ECDH-ES example of https://www.rfc-editor.org/rfc/rfc7518.html#appendix-C,
+        // Bob's static key is kept in the wallet, Alice's ephemeral key is the peer
+        let b64 = |s: &str| base64::decode_config(s, base64::URL_SAFE_NO_PAD);
+        let bob_d = b64("VEmDZpDXXK8p8N0Cndsxs924q6nS1RXFASRl6BfUqdw")?;
+        let alice_epk = [
+            vec![4u8],
+            b64("gI0GAILBdu7T53akrFmMyGcsF3n5dO7MmwNBHKW5SV0")?,
+            b64("SLW_xSffzlPWrHEVI30DHM_4egVwt3NQqeUD7nMFpps")?,
+        ]
+        .concat();
+        let mut bob = UnlockedWallet::new("bob");
+        let bob_kp = KeyPair::new(KeyType::JwsVerificationKey2020, &bob_d)?;
+        assert_eq!(
+            bob_kp.public_key.public_key[1..],
+            b64("weNJy2HscCSM6AEDTDg04biOvhFhyyWvOHQfeF_PxMQ")?[..]
+        );
+        let bob_ref = bob.import_content(&Content::KeyPair(bob_kp)).unwrap().id;
+
+        let kdf = KeyAgreementKdf::ConcatKdf {
+            algorithm_id: b"A128GCM".to_vec(),
+            party_u_info: b"Alice".to_vec(),
+            party_v_info: b"Bob".to_vec(),
+        };
+        let key = bob.derive_shared_key(&bob_ref, &alice_epk, &kdf, 16)?;
+        assert_eq!(
+            base64::encode_config(&key[..], base64::URL_SAFE_NO_PAD),
+            "VqqN6vgjbSBcIijNcacQGg"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn x25519_key_converted_from_ed25519() -> Result<(), Error> {
+        let kdf = test_kdf();
+        let mut w = UnlockedWallet::new("did:key identity");
+        let signing = w.new_key(KeyType::Ed25519VerificationKey2018, None)?;
+        let signing_pk = match signing.content {
+            Content::PublicKey(pk) => pk,
+            _ => unreachable!(),
+        };
+        let agreement = w.x25519_key_pair(&signing.id)?;
+        assert_eq!(
+            agreement.public_key.key_type,
+            KeyType::X25519KeyAgreementKey2019
+        );
+        assert_eq!(
+            agreement.public_key.public_key,
+            signing_pk.to_x25519()?.public_key
+        );
+        // converted key is not stored
+        assert_eq!(w.get_keys().len(), 1);
+
+        let unlocked = w.lock_with(b"password", kdf)?.unlock(b"password")?;
+        let peer = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+        let converted = unlocked.x25519_key_pair(&signing.id)?;
+        assert_eq!(
+            converted.ecdh_key_agreement(&peer.public_key.public_key)?,
+            peer.ecdh_key_agreement(&agreement.public_key.public_key)?
+        );
+        let cipher = signing_pk.to_x25519()?.encrypt(b"secret", None)?;
+        assert_eq!(converted.decrypt(&cipher, None)?, b"secret");
+        Ok(())
+    }
 }
@@ -0,0 +1,324 @@
+use super::{check_slots, Cipher, LockedWallet, WrappedKey, VERSION};
+use crate::{kdf::Kdf, Error};
+use std::convert::TryInto;
+
+/// Magic prefix of the binary `LockedWallet` envelope
+pub const MAGIC: &[u8; 4] = b"UWLT";
+
+impl LockedWallet {
+    /// Encodes the wallet into self-describing binary envelope:
+    /// `MAGIC | version | id | kdf | salt | cipher | nonce | keys | ciphertext`,
+    /// where variable size fields are prefixed with their length.
+    /// `kdf` and `salt` are present up to version `1`, `keys` from version `2`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut out = MAGIC.to_vec();
+        out.push(self.version);
+        let id_len: u16 = self
+            .id
+            .len()
+            .try_into()
+            .map_err(|_| Error::EnvelopeFieldTooLong("id"))?;
+        out.extend_from_slice(&id_len.to_be_bytes());
+        out.extend_from_slice(self.id.as_bytes());
+        if self.version < 2 {
+            push_kdf(&mut out, &self.kdf.ok_or(Error::UnknownEnvelopeFormat)?);
+            push_short(&mut out, &self.salt, "salt")?;
+        }
+        out.push(match self.cipher {
+            Cipher::XChaCha20Poly1305 => 1,
+        });
+        push_short(&mut out, &self.nonce, "nonce")?;
+        if self.version >= 2 {
+            out.push(
+                self.keys
+                    .len()
+                    .try_into()
+                    .map_err(|_| Error::EnvelopeFieldTooLong("keys"))?,
+            );
+            for key in &self.keys {
+                match key {
+                    WrappedKey::Password {
+                        kdf,
+                        salt,
+                        nonce,
+                        ciphertext,
+                    }
+                    | WrappedKey::RecoveryCode {
+                        kdf,
+                        salt,
+                        nonce,
+                        ciphertext,
+                    } => {
+                        out.push(match key {
+                            WrappedKey::Password { .. } => 1,
+                            _ => 2,
+                        });
+                        push_kdf(&mut out, kdf);
+                        push_short(&mut out, salt, "salt")?;
+                        push_short(&mut out, nonce, "nonce")?;
+                        push_short(&mut out, ciphertext, "keys")?;
+                    }
+                    WrappedKey::X25519 {
+                        recipient,
+                        ciphertext,
+                    } => {
+                        out.push(3);
+                        push_short(&mut out, recipient, "recipient")?;
+                        push_short(&mut out, ciphertext, "keys")?;
+                    }
+                    WrappedKey::Shares {
+                        threshold,
+                        nonce,
+                        ciphertext,
+                    } => {
+                        out.push(4);
+                        out.push(*threshold);
+                        push_short(&mut out, nonce, "nonce")?;
+                        push_short(&mut out, ciphertext, "keys")?;
+                    }
+                }
+            }
+        }
+        out.extend_from_slice(&self.ciphertext);
+        Ok(out)
+    }
+
+    /// Parses binary envelope produced by `to_bytes`
+    ///
+    /// # Parameters
+    ///
+    /// * bytes - encoded envelope
+    ///
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut r = Reader(bytes);
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(Error::UnknownEnvelopeFormat);
+        }
+        let version = r.u8()?;
+        if version > VERSION {
+            return Err(Error::UnsupportedEnvelopeVersion(version));
+        }
+        let id_len = u16::from_be_bytes(r.array()?) as usize;
+        let id = std::str::from_utf8(r.take(id_len)?)?.to_string();
+        let (kdf, salt) = if version < 2 {
+            (Some(r.kdf()?), r.short()?.to_vec())
+        } else {
+            (None, vec![])
+        };
+        let cipher = match r.u8()? {
+            1 => Cipher::XChaCha20Poly1305,
+            unknown => return Err(Error::UnknownCipher(unknown)),
+        };
+        let nonce = r.short()?.to_vec();
+        let mut keys = vec![];
+        if version >= 2 {
+            for _ in 0..r.u8()? {
+                keys.push(match r.u8()? {
+                    tag @ 1..=2 => {
+                        let kdf = r.kdf()?;
+                        let salt = r.short()?.to_vec();
+                        let nonce = r.short()?.to_vec();
+                        let ciphertext = r.short()?.to_vec();
+                        if tag == 1 {
+                            WrappedKey::Password {
+                                kdf,
+                                salt,
+                                nonce,
+                                ciphertext,
+                            }
+                        } else {
+                            WrappedKey::RecoveryCode {
+                                kdf,
+                                salt,
+                                nonce,
+                                ciphertext,
+                            }
+                        }
+                    }
+                    3 => WrappedKey::X25519 {
+                        recipient: r.short()?.to_vec(),
+                        ciphertext: r.short()?.to_vec(),
+                    },
+                    4 => WrappedKey::Shares {
+                        threshold: r.u8()?,
+                        nonce: r.short()?.to_vec(),
+                        ciphertext: r.short()?.to_vec(),
+                    },
+                    _ => return Err(Error::UnknownEnvelopeFormat),
+                });
+            }
+            check_slots(&keys)?;
+        }
+        Ok(Self {
+            id,
+            version,
+            kdf,
+            salt,
+            cipher,
+            nonce,
+            keys,
+            ciphertext: r.0.to_vec(),
+        })
+    }
+}
+
+fn push_kdf(out: &mut Vec<u8>, kdf: &Kdf) {
+    match *kdf {
+        Kdf::Sha3 => out.push(0),
+        Kdf::Argon2id {
+            memory,
+            iterations,
+            parallelism,
+        } => {
+            out.push(1);
+            out.extend_from_slice(&memory.to_be_bytes());
+            out.extend_from_slice(&iterations.to_be_bytes());
+            out.extend_from_slice(&parallelism.to_be_bytes());
+        }
+        Kdf::Scrypt { log_n, r, p } => {
+            out.push(2);
+            out.push(log_n);
+            out.extend_from_slice(&r.to_be_bytes());
+            out.extend_from_slice(&p.to_be_bytes());
+        }
+    }
+}
+
+// appends field prefixed with its single byte length
+fn push_short(out: &mut Vec<u8>, field: &[u8], name: &'static str) -> Result<(), Error> {
+    let len: u8 = field
+        .len()
+        .try_into()
+        .map_err(|_| Error::EnvelopeFieldTooLong(name))?;
+    out.push(len);
+    out.extend_from_slice(field);
+    Ok(())
+}
+
+// bounds checked cursor over binary envelope
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(Error::EnvelopeTruncated);
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        Ok(self.take(N)?.try_into().expect("length checked by take"))
+    }
+
+    fn short(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.u8()? as usize;
+        self.take(len)
+    }
+
+    // parameters are bounds checked, so crafted envelope can't exhaust resources
+    fn kdf(&mut self) -> Result<Kdf, Error> {
+        let kdf = match self.u8()? {
+            0 => Kdf::Sha3,
+            1 => Kdf::Argon2id {
+                memory: u32::from_be_bytes(self.array()?),
+                iterations: u32::from_be_bytes(self.array()?),
+                parallelism: u32::from_be_bytes(self.array()?),
+            },
+            2 => Kdf::Scrypt {
+                log_n: self.u8()?,
+                r: u32::from_be_bytes(self.array()?),
+                p: u32::from_be_bytes(self.array()?),
+            },
+            unknown => return Err(Error::UnknownKdf(unknown)),
+        };
+        kdf.check_bounds()?;
+        Ok(kdf)
+    }
+}
+
+#[cfg(test)]
+use crate::kdf::test_kdf;
+
+#[test]
+fn envelope_binary_round_trip() -> Result<(), Error> {
+    use crate::{contents::key_pair::KeyPair, prelude::KeyType, unlocked::UnlockedWallet};
+    let recipient = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let locked = UnlockedWallet::new("did:example:123")
+        .lock_with(b"password", test_kdf())?
+        .add_recovery_code(b"password", b"recovery", test_kdf())?
+        .add_recipient(b"password", &recipient.public_key)?;
+
+    let bytes = locked.to_bytes()?;
+    assert_eq!(&bytes[..4], MAGIC);
+    let parsed = LockedWallet::from_bytes(&bytes)?;
+    assert_eq!(parsed, locked);
+    assert_eq!(parsed.unlock(b"password")?.id, "did:example:123");
+
+    let legacy = LockedWallet::new("legacy", vec![1, 2, 3]);
+    assert_eq!(LockedWallet::from_bytes(&legacy.to_bytes()?)?, legacy);
+    Ok(())
+}
+
+#[test]
+fn envelope_rejects_malformed_input() -> Result<(), Error> {
+    use crate::unlocked::UnlockedWallet;
+    let locked = UnlockedWallet::new("wallet").lock_with(b"password", test_kdf())?;
+    let bytes = locked.to_bytes()?;
+    let header_len = bytes.len() - locked.ciphertext.len();
+
+    assert!(matches!(
+        LockedWallet::from_bytes(b"JUNK"),
+        Err(Error::UnknownEnvelopeFormat)
+    ));
+    // every truncation of the header is reported, never panics
+    for len in 0..header_len {
+        assert!(LockedWallet::from_bytes(&bytes[..len]).is_err());
+    }
+    let mut future = bytes.clone();
+    future[4] = VERSION + 1;
+    assert!(matches!(
+        LockedWallet::from_bytes(&future),
+        Err(Error::UnsupportedEnvelopeVersion(_))
+    ));
+    // JSON envelopes of unknown version are rejected on unlock and rekey
+    let mut future_json = serde_json::to_value(&locked)?;
+    future_json["version"] = (VERSION + 1).into();
+    let future_json: LockedWallet = serde_json::from_value(future_json)?;
+    assert!(matches!(
+        future_json.unlock(b"password"),
+        Err(Error::UnsupportedEnvelopeVersion(v)) if v == VERSION + 1
+    ));
+    assert!(matches!(
+        future_json.rekey(b"password", b"new"),
+        Err(Error::UnsupportedEnvelopeVersion(v)) if v == VERSION + 1
+    ));
+    // cipher tag follows magic, version, id length and 6 bytes of id
+    let mut unknown_cipher = bytes.clone();
+    unknown_cipher[13] = 0xff;
+    assert!(matches!(
+        LockedWallet::from_bytes(&unknown_cipher),
+        Err(Error::UnknownCipher(0xff))
+    ));
+    // password key kdf follows nonce, keys count and key tag
+    let kdf_at = 13 + 1 + 1 + 24 + 1 + 1;
+    let mut unknown_kdf = bytes.clone();
+    unknown_kdf[kdf_at] = 0xff;
+    assert!(matches!(
+        LockedWallet::from_bytes(&unknown_kdf),
+        Err(Error::UnknownKdf(0xff))
+    ));
+    // scrypt log_n follows kdf tag
+    let mut huge_kdf = bytes;
+    huge_kdf[kdf_at + 1] = 0xff;
+    assert!(matches!(
+        LockedWallet::from_bytes(&huge_kdf),
+        Err(Error::KdfParameterOutOfRange("log_n"))
+    ));
+    Ok(())
+}
@@ -0,0 +1,590 @@
+use super::contents::{key_pair::KeyPair, public_key_info::PublicKeyInfo};
+use super::kdf::Kdf;
+use super::unlocked::UnlockedWallet;
+use super::Error;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use serde_json::from_str;
+use zeroize::Zeroizing;
+
+mod envelope;
+mod recovery;
+mod wrapped_key;
+
+pub use envelope::MAGIC;
+pub use recovery::RecoveryShare;
+pub use wrapped_key::WrappedKey;
+
+/// Current version of the `LockedWallet` envelope.
+/// Version `0` is the original format with the nonce appended to the ciphertext,
+/// version `1` encrypts the content with the password derived key directly.
+pub const VERSION: u8 = 2;
+
+/// Size of XChaCha20Poly1305 nonce
+const NONCE_SIZE: usize = 24;
+
+/// Number of password wrapped keys tried per unlock.
+/// `with_password` keeps a single one, so extra slots of a crafted
+/// wallet would only multiply the key derivations.
+const MAX_PASSWORD_SLOTS: usize = 1;
+
+/// Number of recovery code wrapped keys an envelope may hold.
+/// Each one costs a key derivation on unlock.
+const MAX_RECOVERY_SLOTS: usize = 8;
+
+/// Number of Shamir shares wrapped keys an envelope may hold.
+/// `split_recovery` replaces the previous one, so there is at most one.
+const MAX_SHARES_SLOTS: usize = 1;
+
+/// Represents wallet in locked (encrypted) state.
+/// Content is encrypted with random data key, copies of which are
+/// wrapped by every factor able to unlock the wallet, so changing
+/// one of them does not require re-encryption of the content.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LockedWallet {
+    /// Wallet ID
+    pub id: String,
+    /// Envelope format version. Absent for wallets locked before versioning.
+    #[serde(default)]
+    pub version: u8,
+    /// Function used to derive content encryption key from the password
+    /// in versions before `2`, `None` afterwards.
+    /// Absent for wallets locked with unsalted SHA3-256.
+    #[serde(default = "legacy_kdf")]
+    pub kdf: Option<Kdf>,
+    /// Salt for the `kdf`
+    #[serde(default)]
+    pub salt: Vec<u8>,
+    /// Cipher used to encrypt the content
+    #[serde(default)]
+    pub cipher: Cipher,
+    /// Nonce used for the encryption. Empty in version `0`
+    #[serde(default)]
+    pub nonce: Vec<u8>,
+    /// Wrapped copies of the data key. Empty before version `2`
+    #[serde(default)]
+    pub keys: Vec<WrappedKey>,
+    /// Encrypted wallet Content
+    pub ciphertext: Vec<u8>,
+}
+
+/// Symmetric cipher used to encrypt the wallet content
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+pub enum Cipher {
+    #[default]
+    XChaCha20Poly1305,
+}
+
+impl LockedWallet {
+    /// Instantiates encrypted wallet from ID and ciphertext
+    /// produced with SHA3-256 hashed password and nonce appended.
+    ///
+    /// # Parameters
+    ///
+    /// * id - `&str` of wallet's ID
+    /// * ct - encrypted content of the wallet
+    ///
+    pub fn new(id: &str, ct: Vec<u8>) -> Self {
+        Self {
+            id: id.to_string(),
+            version: 0,
+            kdf: legacy_kdf(),
+            salt: vec![],
+            cipher: Cipher::XChaCha20Poly1305,
+            nonce: vec![],
+            keys: vec![],
+            ciphertext: ct,
+        }
+    }
+
+    /// Unlocks the wallet into `UnlockedWallet`
+    ///
+    /// # Parameters
+    ///
+    /// * key - password to decrypt content with
+    ///
+    pub fn unlock(&self, key: &[u8]) -> Result<UnlockedWallet, Error> {
+        to_wallet(&self.decrypt(key)?)
+    }
+
+    /// Unlocks the wallet with one of the recovery codes
+    /// added by `add_recovery_code`
+    ///
+    /// # Parameters
+    ///
+    /// * code - recovery code
+    ///
+    pub fn unlock_with_recovery_code(&self, code: &[u8]) -> Result<UnlockedWallet, Error> {
+        check_slots(&self.keys)?;
+        let dek = self.data_key(|k| k.unwrap_recovery_code(code), MAX_RECOVERY_SLOTS)?;
+        to_wallet(&self.decrypt_content(&dek)?)
+    }
+
+    /// Unlocks the wallet with private key of the recipient
+    /// it was sealed to by `UnlockedWallet::lock_to_recipient` or `add_recipient`
+    ///
+    /// # Parameters
+    ///
+    /// * key_pair - `KeyPair` of `X25519KeyAgreementKey2019` type
+    ///
+    pub fn unlock_with(&self, key_pair: &KeyPair) -> Result<UnlockedWallet, Error> {
+        let dek = self.data_key(|k| k.unwrap_recipient(key_pair), self.keys.len())?;
+        to_wallet(&self.decrypt_content(&dek)?)
+    }
+
+    /// Changes the password.
+    /// Only the data key is re-encrypted, content and other factors are kept.
+    /// Keeps current key derivation parameters, wallets locked with
+    /// legacy SHA3-256 are moved to `Kdf::default()`.
+    ///
+    /// # Parameters
+    ///
+    /// * old - current password
+    /// * new - password to lock resulting wallet with
+    ///
+    pub fn rekey(&self, old: &[u8], new: &[u8]) -> Result<LockedWallet, Error> {
+        let kdf = self.password_kdf();
+        self.rekey_with(old, new, kdf)
+    }
+
+    /// Changes the password and key derivation function.
+    /// Passing the same password for `old` and `new` only rotates the `kdf`.
+    ///
+    /// # Parameters
+    ///
+    /// * old - current password
+    /// * new - password to lock resulting wallet with
+    /// * kdf - `Kdf` to derive new encryption key with
+    ///
+    pub fn rekey_with(&self, old: &[u8], new: &[u8], kdf: Kdf) -> Result<LockedWallet, Error> {
+        let (dek, opened) = self.open(old)?;
+        opened.with_password(&dek, new, kdf)
+    }
+
+    /// Adds recovery code able to unlock the wallet
+    ///
+    /// # Parameters
+    ///
+    /// * password - current password
+    /// * code - recovery code to add
+    /// * kdf - `Kdf` to derive key from the recovery code with
+    ///
+    pub fn add_recovery_code(
+        &self,
+        password: &[u8],
+        code: &[u8],
+        kdf: Kdf,
+    ) -> Result<LockedWallet, Error> {
+        let (dek, mut opened) = self.open(password)?;
+        opened
+            .keys
+            .push(WrappedKey::recovery_code(&dek, code, kdf)?);
+        check_slots(&opened.keys)?;
+        Ok(opened)
+    }
+
+    /// Seals the data key to X25519 public key, so holder of the
+    /// private key can unlock the wallet
+    ///
+    /// # Parameters
+    ///
+    /// * password - current password
+    /// * recipient - `PublicKeyInfo` of `X25519KeyAgreementKey2019` type
+    ///
+    pub fn add_recipient(
+        &self,
+        password: &[u8],
+        recipient: &PublicKeyInfo,
+    ) -> Result<LockedWallet, Error> {
+        let (dek, mut opened) = self.open(password)?;
+        opened.keys.push(WrappedKey::recipient(&dek, recipient)?);
+        Ok(opened)
+    }
+
+    // encrypts serialized wallet content with random data key wrapped by the password
+    pub(crate) fn encrypt(id: &str, plaintext: &[u8], key: &[u8], kdf: Kdf) -> Result<Self, Error> {
+        let dek = new_data_key();
+        let keys = vec![WrappedKey::password(&dek, key, kdf)?];
+        Self::seal(id, plaintext, &dek, keys)
+    }
+
+    // encrypts serialized wallet content with random data key sealed to the recipient
+    pub(crate) fn encrypt_to(
+        id: &str,
+        plaintext: &[u8],
+        recipient: &PublicKeyInfo,
+    ) -> Result<Self, Error> {
+        let dek = new_data_key();
+        let keys = vec![WrappedKey::recipient(&dek, recipient)?];
+        Self::seal(id, plaintext, &dek, keys)
+    }
+
+    // decrypts serialized wallet content with the password provided
+    pub(crate) fn decrypt(&self, key: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        match self.version {
+            0 | 1 => self.decrypt_legacy(key),
+            2 => {
+                let dek = self.data_key(|k| k.unwrap_password(key), MAX_PASSWORD_SLOTS)?;
+                self.decrypt_content(&dek)
+            }
+            unknown => Err(Error::UnsupportedEnvelopeVersion(unknown)),
+        }
+    }
+
+    // data key and this wallet in current envelope version, so factors can be changed
+    pub(crate) fn open(&self, password: &[u8]) -> Result<(Zeroizing<Key>, LockedWallet), Error> {
+        match self.version {
+            0 | 1 => {
+                let plaintext = self.decrypt_legacy(password)?;
+                let dek = new_data_key();
+                let keys = vec![WrappedKey::password(&dek, password, self.password_kdf())?];
+                let upgraded = Self::seal(&self.id, &plaintext, &dek, keys)?;
+                Ok((dek, upgraded))
+            }
+            2 => {
+                let dek = self.data_key(|k| k.unwrap_password(password), MAX_PASSWORD_SLOTS)?;
+                Ok((dek, self.clone()))
+            }
+            unknown => Err(Error::UnsupportedEnvelopeVersion(unknown)),
+        }
+    }
+
+    // replaces password wrapped data key
+    pub(crate) fn with_password(
+        mut self,
+        dek: &Key,
+        password: &[u8],
+        kdf: Kdf,
+    ) -> Result<LockedWallet, Error> {
+        let wrapped = WrappedKey::password(dek, password, kdf)?;
+        self.keys
+            .retain(|k| !matches!(k, WrappedKey::Password { .. }));
+        self.keys.insert(0, wrapped);
+        Ok(self)
+    }
+
+    // replaces content keeping wrapped data keys
+    pub(crate) fn with_content(self, dek: &Key, plaintext: &[u8]) -> Result<LockedWallet, Error> {
+        Self::seal(&self.id, plaintext, dek, self.keys)
+    }
+
+    fn seal(id: &str, plaintext: &[u8], dek: &Key, keys: Vec<WrappedKey>) -> Result<Self, Error> {
+        let nonce = get_nonce();
+        let ciphertext = XChaCha20Poly1305::new(dek)
+            .encrypt(&nonce, plaintext)
+            .map_err(Error::AeadCryptoError)?;
+        Ok(Self {
+            id: id.to_string(),
+            version: VERSION,
+            kdf: None,
+            salt: vec![],
+            cipher: Cipher::XChaCha20Poly1305,
+            nonce: nonce.to_vec(),
+            keys,
+            ciphertext,
+        })
+    }
+
+    // tries up to `limit` wrapped keys `unwrap` applies to
+    fn data_key<F>(&self, unwrap: F, limit: usize) -> Result<Zeroizing<Key>, Error>
+    where
+        F: Fn(&WrappedKey) -> Option<Result<Zeroizing<Key>, Error>>,
+    {
+        let mut result = Err(Error::KeyNotFound);
+        for attempt in self.keys.iter().filter_map(unwrap).take(limit) {
+            if attempt.is_ok() {
+                return attempt;
+            }
+            result = attempt;
+        }
+        result
+    }
+
+    fn decrypt_content(&self, dek: &Key) -> Result<Zeroizing<Vec<u8>>, Error> {
+        if self.nonce.len() != NONCE_SIZE {
+            return Err(Error::EnvelopeTruncated);
+        }
+        match self.cipher {
+            Cipher::XChaCha20Poly1305 => XChaCha20Poly1305::new(dek)
+                .decrypt(XNonce::from_slice(&self.nonce), &self.ciphertext[..])
+                .map(Zeroizing::new)
+                .map_err(Error::AeadCryptoError),
+        }
+    }
+
+    // versions `0` and `1` encrypt the content with password derived key
+    fn decrypt_legacy(&self, key: &[u8]) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let (nonce, content) = match self.version {
+            0 => {
+                if self.ciphertext.len() < NONCE_SIZE {
+                    return Err(Error::EnvelopeTruncated);
+                }
+                let nonce_start = self.ciphertext.len() - NONCE_SIZE;
+                (
+                    &self.ciphertext[nonce_start..],
+                    &self.ciphertext[..nonce_start],
+                )
+            }
+            1 => {
+                if self.nonce.len() != NONCE_SIZE {
+                    return Err(Error::EnvelopeTruncated);
+                }
+                (&self.nonce[..], &self.ciphertext[..])
+            }
+            unknown => return Err(Error::UnsupportedEnvelopeVersion(unknown)),
+        };
+
+        let kdf = self.kdf.ok_or(Error::UnknownEnvelopeFormat)?;
+        let pass = kdf.derive_key(key, &self.salt)?;
+        match self.cipher {
+            Cipher::XChaCha20Poly1305 => XChaCha20Poly1305::new(&pass)
+                .decrypt(XNonce::from_slice(nonce), content)
+                .map(Zeroizing::new)
+                .map_err(Error::AeadCryptoError),
+        }
+    }
+
+    // kdf the password is currently protected with, legacy SHA3-256 replaced by default
+    fn password_kdf(&self) -> Kdf {
+        let current = self.kdf.or_else(|| {
+            self.keys.iter().find_map(|k| match k {
+                WrappedKey::Password { kdf, .. } => Some(*kdf),
+                _ => None,
+            })
+        });
+        match current {
+            Some(Kdf::Sha3) | None => Kdf::default(),
+            Some(kdf) => kdf,
+        }
+    }
+}
+
+// fails if envelope holds more recovery wrapped keys than unlock tries
+fn check_slots(keys: &[WrappedKey]) -> Result<(), Error> {
+    let recovery_codes = keys
+        .iter()
+        .filter(|k| matches!(k, WrappedKey::RecoveryCode { .. }))
+        .count();
+    if recovery_codes > MAX_RECOVERY_SLOTS {
+        return Err(Error::TooManyWrappedKeys("recovery code"));
+    }
+    let shares = keys
+        .iter()
+        .filter(|k| matches!(k, WrappedKey::Shares { .. }))
+        .count();
+    if shares > MAX_SHARES_SLOTS {
+        return Err(Error::TooManyWrappedKeys("shares"));
+    }
+    Ok(())
+}
+
+// wallets serialized without `kdf` were locked with plain SHA3-256
+fn legacy_kdf() -> Option<Kdf> {
+    Some(Kdf::Sha3)
+}
+
+// generates random content encryption key
+fn new_data_key() -> Zeroizing<Key> {
+    let mut key = Zeroizing::new(Key::default());
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+// generates random `XNonce`
+fn get_nonce() -> XNonce {
+    let mut base = [0u8; NONCE_SIZE];
+    OsRng.fill_bytes(&mut base);
+    *XNonce::from_slice(&base)
+}
+
+fn to_wallet(dec: &[u8]) -> Result<UnlockedWallet, Error> {
+    let as_str = std::str::from_utf8(dec).map_err(Error::Utf8)?;
+
+    from_str(as_str).map_err(Error::Serde)
+}
+
+#[cfg(test)]
+use crate::kdf::test_kdf;
+
+#[test]
+fn unlock_legacy_sha3_wallet() -> Result<(), Error> {
+    use sha3::{Digest, Sha3_256};
+    // ciphertext || nonce produced by the SHA3-256 based `lock`
+    let pass = Sha3_256::digest(b"password");
+    let nonce = XNonce::from_slice(&[7u8; 24]);
+    let mut ct = XChaCha20Poly1305::new(&pass)
+        .encrypt(
+            nonce,
+            &br#"{"@context":[],"id":"old","type":[],"contents":[]}"#[..],
+        )
+        .map_err(Error::AeadCryptoError)?;
+    ct.extend_from_slice(nonce);
+    let json = serde_json::to_string(&serde_json::json!({ "id": "old", "ciphertext": ct }))?;
+
+    let locked: LockedWallet = serde_json::from_str(&json)?;
+    assert_eq!(locked.kdf, Some(Kdf::Sha3));
+    assert_eq!(locked.version, 0);
+    let unlocked = locked.unlock(b"password")?;
+    assert_eq!(unlocked.id, "old");
+    assert!(locked.unlock(b"wrong").is_err());
+
+    // locking again moves the wallet to the current envelope and default KDF
+    let relocked = unlocked.lock(b"password")?;
+    assert_eq!(relocked.kdf, None);
+    assert_eq!(relocked.version, VERSION);
+    assert_eq!(relocked.password_kdf(), Kdf::default());
+    assert_eq!(relocked.unlock(b"password")?.id, "old");
+
+    // adding a factor upgrades the envelope too
+    let upgraded = locked.add_recovery_code(b"password", b"code", test_kdf())?;
+    assert_eq!(upgraded.version, VERSION);
+    assert_eq!(upgraded.unlock(b"password")?.id, "old");
+    assert_eq!(upgraded.unlock_with_recovery_code(b"code")?.id, "old");
+
+    // legacy ciphertext shorter than nonce used to underflow
+    assert!(matches!(
+        LockedWallet::new("short", vec![0u8; 10]).unlock(b"password"),
+        Err(Error::EnvelopeTruncated)
+    ));
+    Ok(())
+}
+
+#[test]
+fn rekey_changes_password() -> Result<(), Error> {
+    let locked = UnlockedWallet::new("wallet").lock_with(b"old", test_kdf())?;
+
+    assert!(locked.rekey(b"wrong", b"new").is_err());
+    let rekeyed = locked.rekey(b"old", b"new")?;
+    assert_eq!(rekeyed.password_kdf(), test_kdf());
+    // only the data key is rewrapped
+    assert_eq!(rekeyed.ciphertext, locked.ciphertext);
+    assert_ne!(rekeyed.keys, locked.keys);
+    assert!(rekeyed.unlock(b"old").is_err());
+    assert_eq!(rekeyed.unlock(b"new")?.id, "wallet");
+
+    let rotated_kdf = Kdf::Scrypt {
+        log_n: 5,
+        r: 8,
+        p: 1,
+    };
+    let rotated = rekeyed.rekey_with(b"new", b"new", rotated_kdf)?;
+    assert_eq!(rotated.password_kdf(), rotated_kdf);
+    assert_eq!(rotated.unlock(b"new")?.id, "wallet");
+    Ok(())
+}
+
+#[test]
+fn extra_password_slots_are_not_tried() -> Result<(), Error> {
+    let mut locked = UnlockedWallet::new("wallet").lock_with(b"password", test_kdf())?;
+    locked.keys.insert(
+        0,
+        WrappedKey::password(&new_data_key(), b"other", test_kdf())?,
+    );
+    assert!(locked.unlock(b"password").is_err());
+    assert!(locked.rekey(b"password", b"new").is_err());
+    Ok(())
+}
+
+#[test]
+fn recovery_slots_are_capped() -> Result<(), Error> {
+    let mut locked = UnlockedWallet::new("wallet").lock_with(b"password", test_kdf())?;
+    for _ in 0..MAX_RECOVERY_SLOTS {
+        locked = locked.add_recovery_code(b"password", b"code", test_kdf())?;
+    }
+    assert!(matches!(
+        locked.add_recovery_code(b"password", b"code", test_kdf()),
+        Err(Error::TooManyWrappedKeys(_))
+    ));
+    locked.keys.push(WrappedKey::recovery_code(
+        &new_data_key(),
+        b"code",
+        test_kdf(),
+    )?);
+    assert!(matches!(
+        locked.unlock_with_recovery_code(b"code"),
+        Err(Error::TooManyWrappedKeys(_))
+    ));
+    assert!(matches!(
+        LockedWallet::from_bytes(&locked.to_bytes()?),
+        Err(Error::TooManyWrappedKeys(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn unlock_with_any_factor() -> Result<(), Error> {
+    use crate::{contents::key_pair::KeyPair, prelude::KeyType};
+    let recipient = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let locked = UnlockedWallet::new("wallet")
+        .lock_with(b"password", test_kdf())?
+        .add_recovery_code(b"password", b"first code", test_kdf())?
+        .add_recovery_code(b"password", b"second code", test_kdf())?
+        .add_recipient(b"password", &recipient.public_key)?;
+    assert_eq!(locked.keys.len(), 4);
+    assert!(locked
+        .add_recovery_code(b"wrong", b"code", test_kdf())
+        .is_err());
+
+    assert_eq!(
+        locked.unlock_with_recovery_code(b"first code")?.id,
+        "wallet"
+    );
+    assert_eq!(
+        locked.unlock_with_recovery_code(b"second code")?.id,
+        "wallet"
+    );
+    assert!(locked.unlock_with_recovery_code(b"password").is_err());
+    assert!(locked.unlock(b"first code").is_err());
+
+    // password change keeps the other factors
+    let rekeyed = locked.rekey(b"password", b"new password")?;
+    assert_eq!(rekeyed.keys.len(), 4);
+    assert_eq!(
+        rekeyed.unlock_with_recovery_code(b"first code")?.id,
+        "wallet"
+    );
+    assert_eq!(rekeyed.unlock(b"new password")?.id, "wallet");
+
+    // wallet without recovery codes
+    let plain = UnlockedWallet::new("wallet").lock_with(b"password", test_kdf())?;
+    assert!(matches!(
+        plain.unlock_with_recovery_code(b"code"),
+        Err(Error::KeyNotFound)
+    ));
+    Ok(())
+}
+
+#[test]
+fn lock_to_recipient() -> Result<(), Error> {
+    use crate::prelude::KeyType;
+    let device = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let other = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let locked = UnlockedWallet::new("agent wallet").lock_to_recipient(&device.public_key)?;
+
+    assert_eq!(locked.unlock_with(&device)?.id, "agent wallet");
+    assert!(matches!(
+        locked.unlock_with(&other),
+        Err(Error::KeyNotFound)
+    ));
+    assert!(matches!(
+        locked.unlock(b"password"),
+        Err(Error::KeyNotFound)
+    ));
+    assert_eq!(
+        LockedWallet::from_bytes(&locked.to_bytes()?)?
+            .unlock_with(&device)?
+            .id,
+        "agent wallet"
+    );
+
+    let signing = KeyPair::random_pair(KeyType::Ed25519VerificationKey2018)?;
+    assert!(matches!(
+        UnlockedWallet::new("agent wallet").lock_to_recipient(&signing.public_key),
+        Err(Error::WrongKeyType)
+    ));
+    Ok(())
+}
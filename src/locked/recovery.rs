@@ -0,0 +1,218 @@
+use super::{check_slots, new_data_key, to_wallet, LockedWallet, WrappedKey, MAX_SHARES_SLOTS};
+use crate::{
+    contents::{key_pair::KeyPair, public_key_info::PublicKeyInfo},
+    unlocked::UnlockedWallet,
+    Error,
+};
+use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use std::convert::TryFrom;
+use zeroize::Zeroizing;
+
+/// Shamir share of the secret able to unlock `LockedWallet`.
+/// Shares are handed out to guardians, any `threshold` of them
+/// unlock the wallet with `LockedWallet::unlock_with_shares`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryShare {
+    /// ID of the wallet share belongs to
+    pub id: String,
+    /// Number of shares required to unlock the wallet
+    pub threshold: u8,
+    /// X25519 public key of the guardian share is sealed to.
+    /// `None` for plain shares.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<Vec<u8>>,
+    /// Share itself, encrypted if `recipient` is present.
+    /// Wiped from memory on drop.
+    pub share: Zeroizing<Vec<u8>>,
+}
+
+impl RecoveryShare {
+    /// Encrypts the share to the guardian, so it can be stored
+    /// or transferred without exposing it
+    ///
+    /// # Parameters
+    ///
+    /// * guardian - `PublicKeyInfo` of `X25519KeyAgreementKey2019` type
+    ///
+    pub fn seal_to(&self, guardian: &PublicKeyInfo) -> Result<Self, Error> {
+        if self.is_sealed() {
+            return Err(Error::RecoveryShareSealed);
+        }
+        Ok(Self {
+            recipient: Some(guardian.public_key.clone()),
+            share: Zeroizing::new(guardian.encrypt(&self.share, None)?),
+            ..self.clone()
+        })
+    }
+
+    /// Decrypts share sealed by `seal_to`. Plain shares are returned as is.
+    ///
+    /// # Parameters
+    ///
+    /// * key_pair - guardian's `KeyPair` of `X25519KeyAgreementKey2019` type
+    ///
+    pub fn unseal(&self, key_pair: &KeyPair) -> Result<Self, Error> {
+        match &self.recipient {
+            None => Ok(self.clone()),
+            Some(recipient) if *recipient == key_pair.public_key.public_key => Ok(Self {
+                recipient: None,
+                share: Zeroizing::new(key_pair.decrypt(&self.share, None)?),
+                ..self.clone()
+            }),
+            Some(_) => Err(Error::KeyNotFound),
+        }
+    }
+
+    /// Whether share is encrypted to a guardian
+    pub fn is_sealed(&self) -> bool {
+        self.recipient.is_some()
+    }
+}
+
+impl LockedWallet {
+    /// Splits new recovery secret into `shares` Shamir shares,
+    /// any `threshold` of which unlock the wallet.
+    /// Shares produced by previous split stop working.
+    ///
+    /// # Parameters
+    ///
+    /// * password - current password
+    /// * threshold - number of shares required to unlock
+    /// * shares - total number of shares
+    ///
+    pub fn split_recovery(
+        &self,
+        password: &[u8],
+        threshold: u8,
+        shares: u8,
+    ) -> Result<(LockedWallet, Vec<RecoveryShare>), Error> {
+        if threshold == 0 || threshold > shares {
+            return Err(Error::RecoveryShareError(format!(
+                "threshold {} out of 1..={}",
+                threshold, shares
+            )));
+        }
+        let (dek, mut opened) = self.open(password)?;
+        let secret = new_data_key();
+        opened
+            .keys
+            .retain(|k| !matches!(k, WrappedKey::Shares { .. }));
+        opened
+            .keys
+            .push(WrappedKey::shares(&dek, &secret, threshold)?);
+        let shares = Sharks(threshold)
+            .dealer(&secret)
+            .take(shares as usize)
+            .map(|share| RecoveryShare {
+                id: self.id.clone(),
+                threshold,
+                recipient: None,
+                share: Zeroizing::new(Vec::from(&share)),
+            })
+            .collect();
+        Ok((opened, shares))
+    }
+
+    /// Unlocks the wallet with at least `threshold` of the shares
+    /// produced by `split_recovery`. Shares of other wallets are ignored.
+    ///
+    /// # Parameters
+    ///
+    /// * shares - unsealed recovery shares
+    ///
+    pub fn unlock_with_shares(&self, shares: &[RecoveryShare]) -> Result<UnlockedWallet, Error> {
+        // sharks wipes parsed shares on drop
+        let mut parsed = vec![];
+        for share in shares.iter().filter(|s| s.id == self.id) {
+            if share.is_sealed() {
+                return Err(Error::RecoveryShareSealed);
+            }
+            parsed.push(
+                Share::try_from(&share.share[..])
+                    .map_err(|e| Error::RecoveryShareError(e.to_string()))?,
+            );
+        }
+        check_slots(&self.keys)?;
+        let dek = self.data_key(|k| k.unwrap_shares(&parsed), MAX_SHARES_SLOTS)?;
+        to_wallet(&self.decrypt_content(&dek)?)
+    }
+}
+
+#[test]
+fn unlock_with_threshold_of_shares() -> Result<(), Error> {
+    let kdf = crate::kdf::test_kdf();
+    let locked = UnlockedWallet::new("wallet").lock_with(b"password", kdf)?;
+    assert!(locked.split_recovery(b"password", 4, 3).is_err());
+    assert!(locked.split_recovery(b"wrong", 2, 3).is_err());
+
+    let (locked, shares) = locked.split_recovery(b"password", 2, 3)?;
+    assert_eq!(shares.len(), 3);
+    assert_eq!(locked.unlock_with_shares(&shares[1..])?.id, "wallet");
+    assert_eq!(
+        locked
+            .unlock_with_shares(&[shares[0].clone(), shares[2].clone()])?
+            .id,
+        "wallet"
+    );
+    assert!(matches!(
+        locked.unlock_with_shares(&shares[..1]),
+        Err(Error::RecoveryShareError(_))
+    ));
+    assert_eq!(
+        LockedWallet::from_bytes(&locked.to_bytes()?)?
+            .unlock_with_shares(&shares)?
+            .id,
+        "wallet"
+    );
+
+    // password change keeps shares working, new split revokes them
+    let rekeyed = locked.rekey(b"password", b"new password")?;
+    assert_eq!(rekeyed.unlock_with_shares(&shares)?.id, "wallet");
+    let (resplit, _) = rekeyed.split_recovery(b"new password", 2, 3)?;
+    assert!(resplit.unlock_with_shares(&shares).is_err());
+
+    // envelopes with extra shares slots are rejected
+    let mut crafted = resplit;
+    crafted.keys.push(rekeyed.keys[1].clone());
+    assert!(matches!(
+        crafted.unlock_with_shares(&shares),
+        Err(Error::TooManyWrappedKeys(_))
+    ));
+    assert!(matches!(
+        LockedWallet::from_bytes(&crafted.to_bytes()?),
+        Err(Error::TooManyWrappedKeys(_))
+    ));
+    Ok(())
+}
+
+#[test]
+fn sealed_recovery_shares() -> Result<(), Error> {
+    use crate::{kdf::test_kdf, prelude::KeyType};
+    let kdf = test_kdf();
+    let alice = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let bob = KeyPair::random_pair(KeyType::X25519KeyAgreementKey2019)?;
+    let (locked, shares) = UnlockedWallet::new("wallet")
+        .lock_with(b"password", kdf)?
+        .split_recovery(b"password", 2, 2)?;
+    let sealed = vec![
+        shares[0].seal_to(&alice.public_key)?,
+        shares[1].seal_to(&bob.public_key)?,
+    ];
+    assert!(sealed[0].seal_to(&bob.public_key).is_err());
+
+    // shares survive serialization
+    let json = serde_json::to_string(&sealed)?;
+    let sealed: Vec<RecoveryShare> = serde_json::from_str(&json)?;
+    assert!(matches!(
+        locked.unlock_with_shares(&sealed),
+        Err(Error::RecoveryShareSealed)
+    ));
+    assert!(matches!(sealed[0].unseal(&bob), Err(Error::KeyNotFound)));
+
+    let unsealed = vec![sealed[0].unseal(&alice)?, sealed[1].unseal(&bob)?];
+    assert_eq!(unsealed, shares);
+    assert_eq!(locked.unlock_with_shares(&unsealed)?.id, "wallet");
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use super::{get_nonce, NONCE_SIZE};
+use crate::{
+    contents::{key_pair::KeyPair, public_key_info::PublicKeyInfo},
+    kdf::Kdf,
+    Error,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use sharks::{Share, Sharks};
+use zeroize::Zeroizing;
+
+/// Copy of the wallet data encryption key, encrypted with one of
+/// the factors able to unlock the wallet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum WrappedKey {
+    /// Data key encrypted with the key derived from user's password
+    #[serde(rename_all = "camelCase")]
+    Password {
+        kdf: Kdf,
+        salt: Vec<u8>,
+        nonce: Vec<u8>,
+        ciphertext: Vec<u8>,
+    },
+    /// Data key encrypted with the key derived from recovery code
+    #[serde(rename_all = "camelCase")]
+    RecoveryCode {
+        kdf: Kdf,
+        salt: Vec<u8>,
+        nonce: Vec<u8>,
+        ciphertext: Vec<u8>,
+    },
+    /// Data key sealed to X25519 public key of the recipient
+    #[serde(rename_all = "camelCase")]
+    X25519 {
+        /// public key the data key is sealed to
+        recipient: Vec<u8>,
+        ciphertext: Vec<u8>,
+    },
+    /// Data key encrypted with random secret split into Shamir shares
+    #[serde(rename_all = "camelCase")]
+    Shares {
+        /// number of shares required to recover the secret
+        threshold: u8,
+        nonce: Vec<u8>,
+        ciphertext: Vec<u8>,
+    },
+}
+
+impl WrappedKey {
+    // wraps data key with the password
+    pub(crate) fn password(dek: &Key, password: &[u8], kdf: Kdf) -> Result<Self, Error> {
+        let (kdf, salt, nonce, ciphertext) = wrap_with_secret(dek, password, kdf)?;
+        Ok(WrappedKey::Password {
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    // wraps data key with the recovery code
+    pub(crate) fn recovery_code(dek: &Key, code: &[u8], kdf: Kdf) -> Result<Self, Error> {
+        let (kdf, salt, nonce, ciphertext) = wrap_with_secret(dek, code, kdf)?;
+        Ok(WrappedKey::RecoveryCode {
+            kdf,
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    // seals data key to X25519 public key
+    pub(crate) fn recipient(dek: &Key, recipient: &PublicKeyInfo) -> Result<Self, Error> {
+        Ok(WrappedKey::X25519 {
+            recipient: recipient.public_key.clone(),
+            ciphertext: recipient.encrypt(dek, None)?,
+        })
+    }
+
+    // wraps data key with secret split into `threshold` of N shares
+    pub(crate) fn shares(dek: &Key, secret: &Key, threshold: u8) -> Result<Self, Error> {
+        let nonce = get_nonce();
+        let ciphertext = XChaCha20Poly1305::new(secret)
+            .encrypt(&nonce, dek.as_slice())
+            .map_err(Error::AeadCryptoError)?;
+        Ok(WrappedKey::Shares {
+            threshold,
+            nonce: nonce.to_vec(),
+            ciphertext,
+        })
+    }
+
+    // unwraps data key if this is password slot
+    pub(crate) fn unwrap_password(&self, password: &[u8]) -> Option<Result<Zeroizing<Key>, Error>> {
+        match self {
+            WrappedKey::Password {
+                kdf,
+                salt,
+                nonce,
+                ciphertext,
+            } => Some(unwrap_with_secret(password, kdf, salt, nonce, ciphertext)),
+            _ => None,
+        }
+    }
+
+    // unwraps data key if this is recovery code slot
+    pub(crate) fn unwrap_recovery_code(
+        &self,
+        code: &[u8],
+    ) -> Option<Result<Zeroizing<Key>, Error>> {
+        match self {
+            WrappedKey::RecoveryCode {
+                kdf,
+                salt,
+                nonce,
+                ciphertext,
+            } => Some(unwrap_with_secret(code, kdf, salt, nonce, ciphertext)),
+            _ => None,
+        }
+    }
+
+    // unwraps data key if this is Shamir shares slot
+    pub(crate) fn unwrap_shares(&self, shares: &[Share]) -> Option<Result<Zeroizing<Key>, Error>> {
+        match self {
+            WrappedKey::Shares {
+                threshold,
+                nonce,
+                ciphertext,
+            } => Some(
+                Sharks(*threshold)
+                    .recover(shares)
+                    .map(Zeroizing::new)
+                    .map_err(|e| Error::RecoveryShareError(e.to_string()))
+                    .and_then(|secret| {
+                        if nonce.len() != NONCE_SIZE {
+                            return Err(Error::EnvelopeTruncated);
+                        }
+                        let secret = to_key(&secret)?;
+                        XChaCha20Poly1305::new(&secret)
+                            .decrypt(XNonce::from_slice(nonce), &ciphertext[..])
+                            .map(Zeroizing::new)
+                            .map_err(Error::AeadCryptoError)
+                    })
+                    .and_then(|dek| to_key(&dek)),
+            ),
+            _ => None,
+        }
+    }
+
+    // unwraps data key if it is sealed to public key of `key_pair`
+    pub(crate) fn unwrap_recipient(
+        &self,
+        key_pair: &KeyPair,
+    ) -> Option<Result<Zeroizing<Key>, Error>> {
+        match self {
+            WrappedKey::X25519 {
+                recipient,
+                ciphertext,
+            } if *recipient == key_pair.public_key.public_key => Some(
+                key_pair
+                    .decrypt(ciphertext, None)
+                    .map(Zeroizing::new)
+                    .and_then(|dek| to_key(&dek)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+// kdf, salt, nonce and ciphertext of secret wrapped key
+type SecretWrap = (Kdf, Vec<u8>, Vec<u8>, Vec<u8>);
+
+// encrypts data key with the key derived from the secret
+fn wrap_with_secret(dek: &Key, secret: &[u8], kdf: Kdf) -> Result<SecretWrap, Error> {
+    if kdf == Kdf::Sha3 {
+        return Err(Error::LegacyKdf);
+    }
+    let salt = kdf.new_salt()?;
+    let kek = kdf.derive_key(secret, &salt)?;
+    let nonce = get_nonce();
+    let ciphertext = XChaCha20Poly1305::new(&kek)
+        .encrypt(&nonce, dek.as_slice())
+        .map_err(Error::AeadCryptoError)?;
+    Ok((kdf, salt, nonce.to_vec(), ciphertext))
+}
+
+// decrypts data key with the key derived from the secret
+fn unwrap_with_secret(
+    secret: &[u8],
+    kdf: &Kdf,
+    salt: &[u8],
+    nonce: &[u8],
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Key>, Error> {
+    if nonce.len() != NONCE_SIZE {
+        return Err(Error::EnvelopeTruncated);
+    }
+    let kek = kdf.derive_key(secret, salt)?;
+    let dek = XChaCha20Poly1305::new(&kek)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map(Zeroizing::new)
+        .map_err(Error::AeadCryptoError)?;
+    to_key(&dek)
+}
+
+// checks unwrapped data key size
+fn to_key(dek: &[u8]) -> Result<Zeroizing<Key>, Error> {
+    if dek.len() != 32 {
+        return Err(Error::WrongKeyLength);
+    }
+    Ok(Zeroizing::new(*Key::from_slice(dek)))
+}
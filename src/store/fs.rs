@@ -0,0 +1,229 @@
+use super::{check_swap_id, WalletStore};
+use crate::{locked::LockedWallet, Error};
+use fs2::FileExt;
+use serde_json::{from_slice, to_vec};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+const EXTENSION: &str = "json";
+// file locked for the duration of every write, never a wallet file
+// as its name has no extension
+const LOCK_FILE: &str = ".lock";
+// longest hex encoded id used as file name, leaves room for
+// the temporary file suffix within the usual 255 bytes limit
+const MAX_HEX_NAME: usize = 128;
+// file name prefix of hashed ids, never produced by hex encoding
+const HASHED_PREFIX: &str = "sha256-";
+
+/// `WalletStore` which keeps every wallet in its own JSON file
+/// inside of a directory.
+/// Writes go to a temporary file which is then renamed over the
+/// previous version, so readers never observe partially written wallet.
+/// Writes hold an exclusive OS lock on the `.lock` file of the directory,
+/// so `compare_and_swap` is atomic between all processes and `FileStore`
+/// instances sharing the directory.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    /// Opens store in the directory provided, creating it if missing
+    ///
+    /// # Parameters
+    ///
+    /// * dir - directory to keep wallet files in
+    ///
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self, Error> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    // exclusively locked lock file of the directory, unlocked when dropped
+    fn lock(&self) -> Result<fs::File, Error> {
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.dir.join(LOCK_FILE))?;
+        file.lock_exclusive()?;
+        Ok(file)
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(file_name(id)).with_extension(EXTENSION)
+    }
+
+    fn read(&self, id: &str) -> Result<Option<LockedWallet>, Error> {
+        match fs::read(self.path(id)) {
+            Ok(raw) => Ok(Some(from_slice(&raw)?)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, wallet: &LockedWallet) -> Result<(), Error> {
+        let tmp = self
+            .dir
+            .join(format!(".{}.{}", file_name(&wallet.id), Uuid::new_v4()));
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&to_vec(wallet)?)?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.path(&wallet.id)).map_err(|e| {
+            let _ = fs::remove_file(&tmp);
+            e.into()
+        })
+    }
+
+    fn remove(&self, id: &str) -> Result<bool, Error> {
+        match fs::remove_file(self.path(id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl WalletStore for FileStore {
+    fn save(&self, wallet: &LockedWallet) -> Result<(), Error> {
+        let _lock = self.lock()?;
+        self.write(wallet)
+    }
+
+    fn load(&self, id: &str) -> Result<Option<LockedWallet>, Error> {
+        self.read(id)
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        let mut ids = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension() != Some(EXTENSION.as_ref()) {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|stem| stem.to_str()) {
+                // hashed ids can't be decoded, so they are read from the wallet
+                Some(stem) if stem.starts_with(HASHED_PREFIX) => fs::read(&path)
+                    .ok()
+                    .and_then(|raw| from_slice::<LockedWallet>(&raw).ok())
+                    .map(|wallet| wallet.id),
+                Some(stem) => hex::decode(stem)
+                    .ok()
+                    .and_then(|id| String::from_utf8(id).ok()),
+                None => None,
+            };
+            if let Some(id) = id {
+                ids.push(id);
+            }
+        }
+        Ok(ids)
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, Error> {
+        let _lock = self.lock()?;
+        self.remove(id)
+    }
+
+    fn compare_and_swap(
+        &self,
+        id: &str,
+        current: Option<&LockedWallet>,
+        new: Option<&LockedWallet>,
+    ) -> Result<(), Error> {
+        check_swap_id(id, new)?;
+        let _lock = self.lock()?;
+        if self.read(id)?.as_ref() != current {
+            return Err(Error::StoreConflict(id.to_string()));
+        }
+        match new {
+            Some(wallet) => self.write(wallet),
+            None => self.remove(id).map(|_| ()),
+        }
+    }
+}
+
+// wallet ids are arbitrary strings (URNs, DIDs), so file names are hex encoded;
+// ids too long for a file name are named by their SHA-256 hash instead
+fn file_name(id: &str) -> String {
+    let name = hex::encode(id);
+    if name.len() <= MAX_HEX_NAME {
+        name
+    } else {
+        format!(
+            "{}{}",
+            HASHED_PREFIX,
+            hex::encode(Sha256::digest(id.as_bytes()))
+        )
+    }
+}
+
+#[test]
+fn file_store() -> Result<(), Error> {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let result = super::check_store(FileStore::open(&dir)?);
+    fs::remove_dir_all(&dir)?;
+    result
+}
+
+#[test]
+fn file_store_long_ids() -> Result<(), Error> {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let store = FileStore::open(&dir)?;
+    let id = format!("did:example:{}", "a".repeat(188));
+    assert_eq!(id.len(), 200);
+    let first = LockedWallet::new(&id, vec![1]);
+    let second = LockedWallet::new(&id, vec![2]);
+
+    store.save(&first)?;
+    assert_eq!(store.load(&id)?, Some(first.clone()));
+    assert_eq!(store.list()?, vec![id.clone()]);
+    store.compare_and_swap(&id, Some(&first), Some(&second))?;
+    assert_eq!(store.load(&id)?, Some(second));
+    assert!(store.delete(&id)?);
+    assert!(store.list()?.is_empty());
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn file_store_swaps_across_instances() -> Result<(), Error> {
+    let dir = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let id = "did:example:shared";
+    let initial = LockedWallet::new(id, vec![0]);
+    FileStore::open(&dir)?.save(&initial)?;
+
+    // every thread swaps through its own instance, as separate processes would
+    let handles: Vec<_> = (1..=8u8)
+        .map(|i| {
+            let (dir, initial) = (dir.clone(), initial.clone());
+            std::thread::spawn(move || {
+                let new = LockedWallet::new(id, vec![i]);
+                match FileStore::open(&dir)
+                    .and_then(|store| store.compare_and_swap(id, Some(&initial), Some(&new)))
+                {
+                    Ok(()) => Ok(true),
+                    Err(Error::StoreConflict(_)) => Ok(false),
+                    // `Error` is not `Send`
+                    Err(e) => Err(e.to_string()),
+                }
+            })
+        })
+        .collect();
+    let mut swapped = 0;
+    for handle in handles {
+        let swap = handle.join().expect("swap thread panicked");
+        if swap.map_err(|e| Error::Other(e.into()))? {
+            swapped += 1;
+        }
+    }
+    assert_eq!(swapped, 1);
+    assert_eq!(FileStore::open(&dir)?.list()?, vec![id.to_string()]);
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
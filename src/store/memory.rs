@@ -0,0 +1,69 @@
+use super::{check_swap_id, WalletStore};
+use crate::{locked::LockedWallet, Error};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+/// Volatile `WalletStore` which keeps wallets in memory.
+/// Mostly useful for tests.
+#[derive(Default)]
+pub struct MemoryStore {
+    wallets: Mutex<HashMap<String, LockedWallet>>,
+}
+
+impl MemoryStore {
+    /// Constructs new empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // poisoned lock still holds consistent map as every update is a single operation
+    fn wallets(&self) -> MutexGuard<'_, HashMap<String, LockedWallet>> {
+        self.wallets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl WalletStore for MemoryStore {
+    fn save(&self, wallet: &LockedWallet) -> Result<(), Error> {
+        self.wallets().insert(wallet.id.clone(), wallet.clone());
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<LockedWallet>, Error> {
+        Ok(self.wallets().get(id).cloned())
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        Ok(self.wallets().keys().cloned().collect())
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, Error> {
+        Ok(self.wallets().remove(id).is_some())
+    }
+
+    fn compare_and_swap(
+        &self,
+        id: &str,
+        current: Option<&LockedWallet>,
+        new: Option<&LockedWallet>,
+    ) -> Result<(), Error> {
+        check_swap_id(id, new)?;
+        let mut wallets = self.wallets();
+        if wallets.get(id) != current {
+            return Err(Error::StoreConflict(id.to_string()));
+        }
+        match new {
+            Some(wallet) => wallets.insert(id.to_string(), wallet.clone()),
+            None => wallets.remove(id),
+        };
+        Ok(())
+    }
+}
+
+#[test]
+fn memory_store() -> Result<(), Error> {
+    super::check_store(MemoryStore::new())
+}
@@ -0,0 +1,138 @@
+mod fs;
+mod memory;
+mod sled;
+
+pub use self::fs::FileStore;
+pub use self::memory::MemoryStore;
+pub use self::sled::SledStore;
+
+use crate::{locked::LockedWallet, unlocked::UnlockedWallet, Error};
+
+/// Persistence backend for `LockedWallet`s.
+/// Wallets are addressed by their `id`.
+pub trait WalletStore {
+    /// Stores the wallet, overwriting any wallet with the same `id`
+    ///
+    /// # Parameters
+    ///
+    /// * wallet - `LockedWallet` to be persisted
+    ///
+    fn save(&self, wallet: &LockedWallet) -> Result<(), Error>;
+
+    /// Loads wallet by its `id`, returns `None` if there is no such wallet
+    ///
+    /// # Parameters
+    ///
+    /// * id - ID of the wallet to be loaded
+    ///
+    fn load(&self, id: &str) -> Result<Option<LockedWallet>, Error>;
+
+    /// Returns IDs of all the stored wallets
+    fn list(&self) -> Result<Vec<String>, Error>;
+
+    /// Removes wallet by its `id`. Returns `true` if the wallet was present
+    ///
+    /// # Parameters
+    ///
+    /// * id - ID of the wallet to be removed
+    ///
+    fn delete(&self, id: &str) -> Result<bool, Error>;
+
+    /// Atomically replaces stored wallet only if it still equals `current`.
+    /// Fails with `Error::StoreConflict` if the wallet was changed by someone else
+    /// since `current` was loaded.
+    ///
+    /// # Parameters
+    ///
+    /// * id - ID of the wallet to be updated
+    /// * current - wallet as it was loaded, `None` if it should not exist yet
+    /// * new - wallet to be stored, `None` to remove it
+    ///
+    fn compare_and_swap(
+        &self,
+        id: &str,
+        current: Option<&LockedWallet>,
+        new: Option<&LockedWallet>,
+    ) -> Result<(), Error>;
+
+    /// Locks the wallet with provided password and stores the result
+    ///
+    /// # Parameters
+    ///
+    /// * wallet - `UnlockedWallet` to be locked and persisted
+    /// * key - password to encrypt content with
+    ///
+    fn lock_and_save(&self, wallet: &UnlockedWallet, key: &[u8]) -> Result<LockedWallet, Error> {
+        let locked = wallet.lock(key)?;
+        self.save(&locked)?;
+        Ok(locked)
+    }
+
+    /// Loads wallet by its `id` and unlocks it with provided password
+    ///
+    /// # Parameters
+    ///
+    /// * id - ID of the wallet to be loaded
+    /// * key - password to decrypt content with
+    ///
+    fn load_and_unlock(&self, id: &str, key: &[u8]) -> Result<UnlockedWallet, Error> {
+        self.load(id)?
+            .ok_or_else(|| Error::WalletNotFound(id.to_string()))?
+            .unlock(key)
+    }
+}
+
+// fails if `new` is meant to be stored under different id
+fn check_swap_id(id: &str, new: Option<&LockedWallet>) -> Result<(), Error> {
+    if matches!(new, Some(w) if w.id != id) {
+        Err(Error::StoreConflict(id.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn check_store<S: WalletStore>(store: S) -> Result<(), Error> {
+    let first = LockedWallet::new("wallet", vec![1]);
+    let second = LockedWallet::new("wallet", vec![2]);
+    let concurrent = LockedWallet::new("wallet", vec![3]);
+
+    assert_eq!(store.load("wallet")?, None);
+    store.save(&first)?;
+    assert_eq!(store.load("wallet")?, Some(first.clone()));
+    assert_eq!(store.list()?, vec!["wallet".to_string()]);
+
+    assert!(store
+        .compare_and_swap("wallet", None, Some(&second))
+        .is_err());
+    store.compare_and_swap("wallet", Some(&first), Some(&second))?;
+    // stale `current` must not overwrite newer content
+    assert!(matches!(
+        store.compare_and_swap("wallet", Some(&first), Some(&concurrent)),
+        Err(Error::StoreConflict(_))
+    ));
+    assert_eq!(store.load("wallet")?, Some(second.clone()));
+
+    store.compare_and_swap("wallet", Some(&second), None)?;
+    assert_eq!(store.load("wallet")?, None);
+    store.compare_and_swap("wallet", None, Some(&first))?;
+    assert!(store.delete("wallet")?);
+    assert!(!store.delete("wallet")?);
+    assert!(store.list()?.is_empty());
+
+    let mut wallet = UnlockedWallet::new("unlocked");
+    wallet.new_key(crate::prelude::KeyType::Ed25519VerificationKey2018, None)?;
+    store.lock_and_save(&wallet, b"password")?;
+    assert_eq!(
+        store
+            .load_and_unlock("unlocked", b"password")?
+            .get_keys()
+            .len(),
+        1
+    );
+    assert!(matches!(
+        store.load_and_unlock("missing", b"password"),
+        Err(Error::WalletNotFound(_))
+    ));
+    Ok(())
+}
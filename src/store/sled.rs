@@ -0,0 +1,96 @@
+use super::{check_swap_id, WalletStore};
+use crate::{locked::LockedWallet, Error};
+use serde_json::{from_slice, to_vec};
+use std::path::Path;
+
+/// `WalletStore` backed by a sled database.
+/// Wallets are keyed by their `id` and stored in JSON form.
+/// sled locks the database exclusively, so only one process can open it;
+/// use `FileStore` if several processes write the same wallets.
+pub struct SledStore {
+    db: sled::Db,
+}
+
+impl SledStore {
+    /// Opens (or creates) sled database at the path provided
+    ///
+    /// # Parameters
+    ///
+    /// * path - location of the database directory
+    ///
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self::from_db(sled::open(path)?))
+    }
+
+    /// Wraps already opened sled database
+    ///
+    /// # Parameters
+    ///
+    /// * db - opened `sled::Db` instance
+    ///
+    pub fn from_db(db: sled::Db) -> Self {
+        Self { db }
+    }
+}
+
+impl WalletStore for SledStore {
+    fn save(&self, wallet: &LockedWallet) -> Result<(), Error> {
+        self.db.insert(wallet.id.as_bytes(), to_vec(wallet)?)?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<LockedWallet>, Error> {
+        match self.db.get(id.as_bytes())? {
+            Some(raw) => Ok(Some(from_slice(&raw)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<String>, Error> {
+        self.db
+            .iter()
+            .keys()
+            .map(|k| Ok(String::from_utf8(k?.to_vec()).map_err(|e| e.utf8_error())?))
+            .collect()
+    }
+
+    fn delete(&self, id: &str) -> Result<bool, Error> {
+        let removed = self.db.remove(id.as_bytes())?.is_some();
+        self.db.flush()?;
+        Ok(removed)
+    }
+
+    fn compare_and_swap(
+        &self,
+        id: &str,
+        current: Option<&LockedWallet>,
+        new: Option<&LockedWallet>,
+    ) -> Result<(), Error> {
+        check_swap_id(id, new)?;
+        // compare against raw stored bytes so wallets written in older
+        //  serialization forms can still be swapped
+        let stored = self.db.get(id.as_bytes())?;
+        let matches = match (&stored, current) {
+            (Some(raw), Some(current)) => &from_slice::<LockedWallet>(raw)? == current,
+            (None, None) => true,
+            _ => false,
+        };
+        if !matches {
+            return Err(Error::StoreConflict(id.to_string()));
+        }
+        let new = new.map(to_vec).transpose()?;
+        self.db
+            .compare_and_swap(id.as_bytes(), stored, new)?
+            .map_err(|_| Error::StoreConflict(id.to_string()))?;
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn sled_store() -> Result<(), Error> {
+    super::check_store(SledStore::from_db(
+        sled::Config::new().temporary(true).open()?,
+    ))
+}
@@ -39,6 +39,7 @@ impl UnlockedWallet {
             if let Some(kp) = ekp {
                 match kp {
                     (_, Content::KeyPair(kp)) => {
+                        let kp = self.resolve_key_pair(kp)?;
                         let mut jws = Message::new()
                             .set_didcomm_header(m.get_didcomm_header().to_owned());
                         let mut key = Jwk::new();
@@ -75,7 +76,7 @@ impl UnlockedWallet {
                         jws.jwm_header.cty = Some(String::from("JWM"));
                         jws.jwm_header.jwk = Some(key);
                         jws = jws.set_body(message.as_bytes());
-                        self.seal_encrypted_str(&jws.sign(alg.signer(), &kp.private_key())?)
+                        self.seal_encrypted_str(&jws.sign(alg.signer(), kp.private_key())?)
                     },
                 _ => Err(Error::KeyNotFound),
                 }
@@ -107,6 +108,7 @@ impl UnlockedWallet {
         if let Some(ekp) = ekp {
             match ekp {
                 (_, Content::KeyPair(ekp)) => {
+                    let ekp = self.resolve_key_pair(ekp)?;
                     let mut e_key = Jwk::new();
                     match ekp.public_key.key_type {
                         KeyType::X25519KeyAgreementKey2019 => {
@@ -123,7 +125,7 @@ impl UnlockedWallet {
                     jwe.jwm_header.jwk = Some(e_key);
                     jwe.set_didcomm_header(message.get_didcomm_header().to_owned())
                         .set_body(serde_json::to_string(&message)?.as_bytes())
-                        .seal(&ekp.private_key())
+                        .seal(ekp.private_key())
                         .map_err(|e| Error::DidcommError(e))
                 },
                 _ => return Err(Error::UnsupportedKeyType)
@@ -140,9 +142,10 @@ impl UnlockedWallet {
         if let Some(kp) = self.get_content_from_header(&jwe.header.kid) {
             match kp {
                 Content::KeyPair(unwrapped_kp) => {
+                    let unwrapped_kp = self.resolve_key_pair(&unwrapped_kp)?;
                     Ok(Message::receive(
                         &String::from_utf8_lossy(msg_bytes),
-                        &unwrapped_kp.private_key(),
+                        unwrapped_kp.private_key(),
                     )?)
                 },
                 _ => return Err(Error::ContentNotFound(jwe.header.kid.iter().map(|s| s.to_owned()).collect::<String>()))
@@ -188,14 +191,16 @@ fn send_receive_test() {
     let mut alice_wallet = UnlockedWallet::new("alice");
     let alice_didkey_content = Content::KeyPair(KeyPair {
         public_key: PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2019, &"7By6kV2t2d188odEM4ExAve1UithKT6dLva4dwsDT3ak".from_base58().unwrap()),
-        private_key: "6QN8DfuN9hjgHgPvLXqgzqYE3jRRGRrmJQZkd5tL8paR".from_base58().unwrap()
+        private_key: "6QN8DfuN9hjgHgPvLXqgzqYE3jRRGRrmJQZkd5tL8paR".from_base58().unwrap().into(),
+        encoding: None
     }.set_controller(vec!("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp".into())));
     let alice_import = alice_wallet.import_content(&alice_didkey_content);
 
     let mut bob_wallet = UnlockedWallet::new("bob");
     let bob_didkey_content = Content::KeyPair(KeyPair {
         public_key: PublicKeyInfo::new(KeyType::X25519KeyAgreementKey2019, &"FcoNC5NqP9CePWbhfz95iHaEsCjGkZUioK9Ck7Qiw286".from_base58().unwrap()),
-        private_key: "HBTcN2MrXNRj9xF9oi8QqYyuEPv3JLLjQKuEgW9oxVKP".from_base58().unwrap()
+        private_key: "HBTcN2MrXNRj9xF9oi8QqYyuEPv3JLLjQKuEgW9oxVKP".from_base58().unwrap().into(),
+        encoding: None
     }.set_controller(vec!("did:key:z6MkjchhfUsD6mmvni8mCdXHw216Xrm9bQe2mBH1P5RDjVJG".into())));
     let bob_import = bob_wallet.import_content(&bob_didkey_content);
 
@@ -1,22 +1,25 @@
 use crate::{
-    contents::{key_pair::KeyPair, public_key_info::KeyType, Content, ContentEntity, Contents},
+    contents::{
+        entropy::Entropy,
+        key_pair::{KeyPair, PrivateKeyFormat},
+        public_key_info::{KeyType, PublicKeyInfo},
+        Content, ContentEntity, Contents, ExportMode,
+    },
+    kdf::{Kdf, KeyAgreementKdf},
     locked::LockedWallet,
     Error,
 };
-use chacha20poly1305::{
-    aead::{Aead, NewAead},
-    XChaCha20Poly1305, XNonce,
-};
-use rand_core::{OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use serde_json::to_string;
-use sha3::{Digest, Sha3_256};
+use std::borrow::Cow;
+use zeroize::Zeroizing;
 
 #[cfg(feature = "didcomm")]
 mod didcomm;
 
-/// Represents unlocked wallet with it's content in raw form
-#[derive(Serialize, Deserialize)]
+/// Represents unlocked wallet with it's content in raw form.
+/// `Debug` output does not contain secret material.
+#[derive(Deserialize, Debug)]
 pub struct UnlockedWallet {
     /// JSON-LD `@context` key-value pair
     #[serde(rename = "@context")]
@@ -30,6 +33,18 @@ pub struct UnlockedWallet {
     contents: Contents,
 }
 
+// JSON shape of `UnlockedWallet`, written only by `lock` and `export`
+// so secrets never leave the wallet through plain `Serialize`
+#[derive(Serialize)]
+struct UnlockedWalletRepr<'a> {
+    #[serde(rename = "@context")]
+    context: &'a [String],
+    id: &'a str,
+    #[serde(rename = "type")]
+    wallet_type: &'a [String],
+    contents: &'a Contents,
+}
+
 impl UnlockedWallet {
     /// Constructs new instance with provided ID
     ///
@@ -64,19 +79,75 @@ impl UnlockedWallet {
         key_controller: Option<Vec<String>>,
     ) -> Result<ContentEntity, Error> {
         let kp = KeyPair::random_pair(key_type).map_err(|e| Error::Other(Box::new(e)))?;
-        let pk = kp.public_key.clone();
-        let key_pair = Content::KeyPair(kp.set_controller(match key_controller {
-            Some(c) => c,
-            None => vec![[
-                        self.id.clone(),
-                        base64::encode_config(pk.public_key, base64::URL_SAFE),
-                    ]
-                    .join("#")],
-        }));
-        self.contents
-            .import(key_pair)
-            .map(|(id, content)| content.to_entity(&id).clean())
-            .ok_or(Error::KeyPairAddFailed)
+        self.import_key(kp, key_controller)
+    }
+
+    /// Derives key pair of type specified from the wallet's `Entropy` and stores it.
+    /// Key is stored as `PrivateKeyFromSeed`, so only the path to it is persisted
+    /// and it is re-derived when the wallet is loaded.
+    ///
+    /// # Parameters
+    ///
+    /// * seed_ref - id of the `Entropy` content to derive from
+    /// * key_type - `KeyType` of the derived key
+    /// * path - derivation path, see `derivation::bip44_path`
+    /// * key_controller - Optional controller information
+    ///
+    pub fn derive_key(
+        &mut self,
+        seed_ref: &str,
+        key_type: KeyType,
+        path: &str,
+        key_controller: Option<Vec<String>>,
+    ) -> Result<ContentEntity, Error> {
+        let kp = KeyPair::derive(key_type, &self.seed(seed_ref)?, path)?.encoding(
+            PrivateKeyFormat::FromSeed {
+                path: path.to_string(),
+                seed_ref: seed_ref.to_string(),
+            },
+        );
+        self.import_key(kp, key_controller)
+    }
+
+    /// Converts Ed25519 key pair selected by refference into X25519 one
+    /// without storing it, see `KeyPair::to_x25519`
+    ///
+    /// # Parameters
+    ///
+    /// * key_ref - Ed25519 key pair to convert
+    ///
+    pub fn x25519_key_pair(&self, key_ref: &str) -> Result<KeyPair, Error> {
+        self.key_pair(key_ref)?.to_x25519()
+    }
+
+    /// Generates new BIP39 mnemonic and stores its entropy in the wallet
+    ///
+    /// # Parameters
+    ///
+    /// * word_count - number of mnemonic words, one of 12, 15, 18, 21 or 24
+    ///
+    /// Returns id of stored `Entropy` and the mnemonic phrase to back up
+    ///
+    pub fn new_mnemonic(
+        &mut self,
+        word_count: usize,
+    ) -> Result<(String, Zeroizing<String>), Error> {
+        let entropy = Entropy::random_mnemonic(word_count)?;
+        let phrase = entropy.mnemonic()?;
+        Ok((self.import_entropy(entropy)?, phrase))
+    }
+
+    /// Restores BIP39 mnemonic entropy in the wallet, so keys derived
+    /// from it can be derived again with `derive_key`
+    ///
+    /// # Parameters
+    ///
+    /// * phrase - mnemonic phrase
+    ///
+    /// Returns id of stored `Entropy`
+    ///
+    pub fn import_mnemonic(&mut self, phrase: &str) -> Result<String, Error> {
+        self.import_entropy(Entropy::from_mnemonic(phrase)?)
     }
 
     /// Imports provided content into wallet
@@ -157,13 +228,45 @@ impl UnlockedWallet {
     /// * data - message to be signed by selected key
     ///
     pub fn sign_raw(&self, key_ref: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
-        match self.contents.get(key_ref) {
-            Some(c) => match &c {
-                Content::KeyPair(k) => k.sign(data),
-                _ => Err(Error::ContentTypeIncorrect),
-            },
-            None => Err(Error::KeyNotFound),
-        }
+        self.key_pair(key_ref)?.sign(data)
+    }
+
+    /// Signs message with RSASSA-PSS (PS256) using the key selected by refference.
+    /// Only `RsaVerificationKey2018` keys are supported.
+    ///
+    /// # Properties
+    ///
+    /// * key_ref - key to be fetched and signed with
+    /// * data - message to be signed by selected key
+    ///
+    pub fn sign_raw_pss(&self, key_ref: &str, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.key_pair(key_ref)?.sign_pss(data)
+    }
+
+    /// Signs list of messages as one BBS+ signature with the key selected by refference
+    ///
+    /// # Properties
+    ///
+    /// * key_ref - `Bls12381G2Key2020` key to be fetched and signed with
+    /// * messages - messages to be signed, holder later discloses them by index
+    ///
+    pub fn sign_bbs<M: AsRef<[u8]>>(
+        &self,
+        key_ref: &str,
+        messages: &[M],
+    ) -> Result<Vec<u8>, Error> {
+        self.key_pair(key_ref)?.sign_bbs(messages)
+    }
+
+    /// Generates proof of possession of BLS key selected by refference,
+    /// to be shared along with the public key before signatures are aggregated.
+    ///
+    /// # Properties
+    ///
+    /// * key_ref - `Bls12381G1Key2020` or `Bls12381G2Key2020` key to prove possession of
+    ///
+    pub fn prove_possession(&self, key_ref: &str) -> Result<Vec<u8>, Error> {
+        self.key_pair(key_ref)?.prove_possession()
     }
 
     /// Decrypts provided cypher text using desired key by refference
@@ -180,58 +283,209 @@ impl UnlockedWallet {
         data: &[u8],
         aad: Option<&[u8]>,
     ) -> Result<Vec<u8>, Error> {
-        match self.contents.get(key_ref) {
-            Some(c) => match &c {
-                Content::KeyPair(k) => k.decrypt(data, aad),
-                _ => Err(Error::ContentTypeIncorrect),
-            },
-            None => Err(Error::KeyNotFound),
-        }
+        self.key_pair(key_ref)?.decrypt(data, aad)
     }
 
-    /// Performs ECDH Key Agreement
+    /// Performs ECDH Key Agreement, see `KeyPair::ecdh_key_agreement`.
+    /// Peer key of wrong length or encoding fails with `Error::InvalidPublicKey`.
     ///
     /// # Parameters
     ///
     /// * key_ref - private key for ECDH
     /// * key - public key for ECDH
-    pub fn ecdh_key_agreement(&self, key_ref: &str, key: &[u8]) -> Result<Vec<u8>, Error> {
-        match self.contents.get(key_ref) {
-            Some(c) => match &c {
-                Content::KeyPair(k) => k.ecdh_key_agreement(key),
-                _ => Err(Error::ContentTypeIncorrect),
-            },
-            None => Err(Error::KeyNotFound),
-        }
+    pub fn ecdh_key_agreement(
+        &self,
+        key_ref: &str,
+        key: &[u8],
+    ) -> Result<Zeroizing<Vec<u8>>, Error> {
+        self.key_pair(key_ref)?.ecdh_key_agreement(key)
+    }
+
+    /// Performs ECDH Key Agreement and derives symmetric key from the shared
+    /// secret, so the raw secret never leaves the wallet
+    ///
+    /// # Parameters
+    ///
+    /// * key_ref - private key for ECDH
+    /// * peer_pk - public key for ECDH
+    /// * kdf - `KeyAgreementKdf` to derive the key with, along with its context
+    /// * len - length of the derived key in bytes
+    ///
+    pub fn derive_shared_key(
+        &self,
+        key_ref: &str,
+        peer_pk: &[u8],
+        kdf: &KeyAgreementKdf,
+        len: usize,
+    ) -> Result<Zeroizing<Vec<u8>>, Error> {
+        let secret = self.ecdh_key_agreement(key_ref, peer_pk)?;
+        kdf.derive_key(&secret, len)
     }
 
-    /// Locks the wallet by encripting all the content and producing `LockedWallet`
+    /// Locks the wallet by encripting all the content and producing `LockedWallet`.
+    /// Encryption key is derived from the password with `Kdf::default()`.
     ///
     /// # Parameters
     ///
     /// * key - secret key (password) for the encription
     ///
     pub fn lock(&self, key: &[u8]) -> Result<LockedWallet, Error> {
-        let mut sha3 = Sha3_256::new();
-        sha3.update(key);
-        let pass = sha3.finalize();
-
-        let cha_cha = XChaCha20Poly1305::new(&pass);
-        let mut nonce = get_nonce(); //XNonce::from_slice(self.id.as_bytes());
-        let mut cypher = cha_cha
-            .encrypt(&nonce, to_string(&self).map_err(Error::Serde)?.as_bytes())
-            .map_err(Error::AeadCryptoError)?;
-        cypher.append(&mut nonce.iter_mut().map(|v| *v).collect());
-        Ok(LockedWallet {
-            id: self.id.clone(),
-            ciphertext: cypher,
-        })
+        self.lock_with(key, Kdf::default())
+    }
+
+    /// Locks the wallet using specific key derivation function
+    ///
+    /// # Parameters
+    ///
+    /// * key - secret key (password) for the encription
+    /// * kdf - `Kdf` to derive encryption key with. `Kdf::Sha3` is not allowed
+    ///
+    pub fn lock_with(&self, key: &[u8], kdf: Kdf) -> Result<LockedWallet, Error> {
+        LockedWallet::encrypt(
+            &self.id,
+            self.to_plaintext(&self.contents)?.as_bytes(),
+            key,
+            kdf,
+        )
+    }
+
+    /// Locks the wallet to X25519 public key instead of a password.
+    /// Resulting wallet is unlocked by `LockedWallet::unlock_with`
+    /// using the private key of the recipient.
+    ///
+    /// # Parameters
+    ///
+    /// * recipient - `PublicKeyInfo` of `X25519KeyAgreementKey2019` type
+    ///
+    pub fn lock_to_recipient(&self, recipient: &PublicKeyInfo) -> Result<LockedWallet, Error> {
+        LockedWallet::encrypt_to(
+            &self.id,
+            self.to_plaintext(&self.contents)?.as_bytes(),
+            recipient,
+        )
+    }
+
+    /// Locks the wallet under new password after checking that `old`
+    /// unlocks currently stored `locked` version of it.
+    /// Other factors able to unlock `locked` are kept.
+    /// Use `LockedWallet::rekey` when wallet does not need to be unlocked.
+    ///
+    /// # Parameters
+    ///
+    /// * locked - currently stored locked version of this wallet
+    /// * old - current password
+    /// * new - password to lock resulting wallet with
+    /// * kdf - `Kdf` to derive new encryption key with
+    ///
+    pub fn rekey(
+        &self,
+        locked: &LockedWallet,
+        old: &[u8],
+        new: &[u8],
+        kdf: Kdf,
+    ) -> Result<LockedWallet, Error> {
+        if locked.id != self.id {
+            return Err(Error::WalletNotFound(self.id.clone()));
+        }
+        let (dek, opened) = locked.open(old)?;
+        opened
+            .with_password(&dek, new, kdf)?
+            .with_content(&dek, self.to_plaintext(&self.contents)?.as_bytes())
+    }
+
+    /// Serializes the wallet into JSON. Wallet does not implement `Serialize`,
+    /// so explicit choice whether secrets are included is required.
+    ///
+    /// # Parameters
+    ///
+    /// * mode - `ExportMode::PublicOnly` to strip private keys and entropy,
+    ///   `ExportMode::Full` for complete backup
+    ///
+    pub fn export(&self, mode: ExportMode) -> Result<Zeroizing<String>, Error> {
+        match mode {
+            ExportMode::Full => self.to_plaintext(&self.contents),
+            ExportMode::PublicOnly => self.to_plaintext(&self.contents.export(mode)),
+        }
+    }
+
+    // stores entropy under new id
+    fn import_entropy(&mut self, entropy: Entropy) -> Result<String, Error> {
+        self.contents
+            .import(Content::Entropy(entropy))
+            .map(|(id, _)| id)
+            .ok_or(Error::KeyInsertionError)
+    }
+
+    // stores key pair with controller provided or generated from the public key
+    fn import_key(
+        &mut self,
+        kp: KeyPair,
+        key_controller: Option<Vec<String>>,
+    ) -> Result<ContentEntity, Error> {
+        let pk = kp.public_key.clone();
+        let key_pair = Content::KeyPair(kp.set_controller(match key_controller {
+            Some(c) => c,
+            None => vec![[
+                        self.id.clone(),
+                        base64::encode_config(pk.public_key, base64::URL_SAFE),
+                    ]
+                    .join("#")],
+        }));
+        self.contents
+            .import(key_pair)
+            .map(|(id, content)| content.to_entity(&id).clean())
+            .ok_or(Error::KeyPairAddFailed)
+    }
+
+    // seed of the `Entropy` stored under `seed_ref`
+    fn seed(&self, seed_ref: &str) -> Result<Zeroizing<Vec<u8>>, Error> {
+        match self.contents.get(seed_ref) {
+            Some(Content::Entropy(entropy)) => entropy.seed(),
+            Some(_) => Err(Error::ContentTypeIncorrect),
+            None => Err(Error::ContentNotFound(seed_ref.to_string())),
+        }
     }
-}
 
-// generates random `XNonce`
-fn get_nonce() -> XNonce {
-    let mut base = [0u8; 24];
-    OsRng.fill_bytes(&mut base);
-    *XNonce::from_slice(&base)
+    // key pair stored under `key_ref`, see `resolve_key_pair`
+    fn key_pair(&self, key_ref: &str) -> Result<Cow<'_, KeyPair>, Error> {
+        match self.contents.get(key_ref) {
+            Some(Content::KeyPair(kp)) => self.resolve_key_pair(kp),
+            Some(_) => Err(Error::ContentTypeIncorrect),
+            None => Err(Error::KeyNotFound),
+        }
+    }
+
+    // key pair with the private key derived from the wallet's `Entropy`
+    // if it is stored as `PrivateKeyFromSeed`
+    fn resolve_key_pair<'a>(&self, kp: &'a KeyPair) -> Result<Cow<'a, KeyPair>, Error> {
+        let derived = match kp.get_encoding() {
+            // `Multikey`s are derived as the type they are prefixed with
+            Some(PrivateKeyFormat::FromSeed { path, seed_ref }) if kp.private_key().is_empty() => {
+                let key_type = kp.public_key.resolve_multikey()?.key_type;
+                KeyPair::derive(key_type, &self.seed(seed_ref)?, path)?
+            }
+            _ => return Ok(Cow::Borrowed(kp)),
+        };
+        if derived.public_key.resolve_multikey()?.public_key
+            != kp.public_key.resolve_multikey()?.public_key
+        {
+            return Err(Error::DerivedKeyMismatch);
+        }
+        Ok(Cow::Owned(KeyPair {
+            private_key: derived.private_key,
+            ..kp.clone()
+        }))
+    }
+
+    // serialized wallet with `contents`, wiped from memory on drop
+    fn to_plaintext(&self, contents: &Contents) -> Result<Zeroizing<String>, Error> {
+        to_string(&UnlockedWalletRepr {
+            context: &self.context,
+            id: &self.id,
+            wallet_type: &self.wallet_type,
+            contents,
+        })
+        .map(Zeroizing::new)
+        .map_err(Error::Serde)
+    }
 }